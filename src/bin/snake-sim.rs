@@ -0,0 +1,103 @@
+use rust_snake::cli::{self, GameOptions};
+use rust_snake::sim::*;
+use std::time::Instant;
+
+// 画面を出さずにゲームをたくさん遊ばせて、結果を集計する
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let options = parse_args()?;
+    let simulation = options.simulation()?;
+    let started = Instant::now();
+    let results = simulation.run();
+    let summary = Summary::new(&simulation, &results);
+    match options.format {
+        Format::Text => {
+            print!("{}", summary.to_text());
+            println!("threads    {}", simulation.threads);
+            println!("elapsed    {:.1}s", started.elapsed().as_secs_f64());
+        }
+        Format::Json => print!("{}", summary.to_json()),
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Default)]
+enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Default)]
+struct Options {
+    game: GameOptions,
+    games: Option<usize>,
+    strategy: Option<Driver>,
+    threads: Option<usize>,
+    frames_max: Option<i32>,
+    format: Format,
+}
+
+impl Options {
+    fn simulation(&self) -> Result<Simulation, String> {
+        let config = self.game.config()?;
+        let simulation = Simulation::new(config, self.strategy.unwrap_or(Driver::Autopilot));
+        Ok(Simulation {
+            games: self.games.unwrap_or(simulation.games),
+            first_seed: self.game.seed.unwrap_or(simulation.first_seed),
+            threads: self.threads.unwrap_or(simulation.threads),
+            frames_max: self.frames_max.unwrap_or(simulation.frames_max),
+            ..simulation
+        })
+    }
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options::default();
+    for (name, value) in cli::split_args(std::env::args().skip(1))? {
+        match name.as_str() {
+            "--games" => options.games = Some(parse_count(&name, &value)?),
+            "--strategy" => {
+                options.strategy = Some(Driver::from_name(&value).ok_or_else(|| {
+                    let names: Vec<&str> = Driver::all().iter().map(|x| x.name()).collect();
+                    format!(
+                        "invalid strategy {:?}\navailable strategies: {}",
+                        value,
+                        names.join(", ")
+                    )
+                })?);
+            }
+            "--threads" => options.threads = Some(parse_count(&name, &value)?),
+            "--frames-max" => {
+                options.frames_max = Some(parse_count(&name, &value)? as i32);
+            }
+            "--format" => {
+                options.format = match value.as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    _ => return Err(format!("invalid format {:?}", value)),
+                };
+            }
+            // 画面もキー操作もなく、遊ぶのは1人だけ
+            "--cell-size" | "--players" | "--keys" => return Err(cli::unknown_argument(&name)),
+            _ if options.game.parse(&name, &value)? => {}
+            _ => return Err(cli::unknown_argument(&name)),
+        }
+    }
+    Ok(options)
+}
+
+// 1以上の数
+fn parse_count(name: &str, value: &str) -> Result<usize, String> {
+    value
+        .parse::<usize>()
+        .ok()
+        .filter(|x| (1..=i32::MAX as usize).contains(x))
+        .ok_or_else(|| format!("invalid {} {:?}", name, value))
+}
@@ -0,0 +1,358 @@
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, DisableFocusChange, EnableFocusChange, Event, KeyCode, KeyEvent, KeyEventKind,
+    KeyModifiers,
+};
+use crossterm::style::{self, Colors, Print, ResetColor, SetColors};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use rust_snake::bindings::{Action, KeyBindings};
+use rust_snake::cli::{self, GameOptions};
+use rust_snake::model::*;
+use rust_snake::render::Color;
+use rust_snake::terminal::{ansi256, Screen, TerminalView, BLACK};
+use rust_snake::timestep::FixedTimestep;
+use std::io::{self, BufWriter, Write};
+use std::time::Instant;
+
+const FPS: u32 = 30;
+// 描くのが遅れたときに1回でまとめて進めるフレーム数の上限
+const MAX_CATCH_UP_TICKS: u32 = 5;
+
+// 端末で遊ぶ。SDL2もウィンドウもいらないのでSSH越しでも遊べる
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let options = parse_args()?;
+    let config = options.game.config()?;
+    let bindings = options.game.bindings()?;
+    let colors = options.colors.unwrap_or_else(ColorMode::detect);
+
+    let mut app = App::new(&options, config, bindings);
+    let raw = RawTerminal::enter()?;
+    let mut out = BufWriter::new(io::stdout());
+    let result = app.run(&mut out, colors);
+    drop(out);
+    // 端末を元に戻してから表示する
+    drop(raw);
+    println!("Seed: {}", app.game.seed);
+    result
+}
+
+// 生きている間だけ端末をrawモードと別画面にする。エラーやpanicで抜けても元に戻す
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> Result<Self, String> {
+        terminal::enable_raw_mode().map_err(|e| e.to_string())?;
+        let raw = RawTerminal;
+        execute!(
+            io::stdout(),
+            EnterAlternateScreen,
+            EnableFocusChange,
+            Hide,
+            Clear(ClearType::All)
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(raw)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = execute!(
+            io::stdout(),
+            ResetColor,
+            Show,
+            DisableFocusChange,
+            LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ColorMode {
+    TrueColor,
+    Ansi256,
+}
+
+impl ColorMode {
+    // COLORTERMはSSHでは渡らないことが多いので、そのときは--colorsで指定する
+    fn detect() -> Self {
+        match std::env::var("COLORTERM").as_deref() {
+            Ok("truecolor" | "24bit") => ColorMode::TrueColor,
+            _ => ColorMode::Ansi256,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "truecolor" => Some(ColorMode::TrueColor),
+            "256" => Some(ColorMode::Ansi256),
+            _ => None,
+        }
+    }
+
+    fn convert(&self, color: Color) -> style::Color {
+        match self {
+            ColorMode::TrueColor => style::Color::Rgb {
+                r: color.r,
+                g: color.g,
+                b: color.b,
+            },
+            ColorMode::Ansi256 => style::Color::AnsiValue(ansi256(color)),
+        }
+    }
+}
+
+enum State {
+    Playing,
+    Paused,
+    GameOver,
+}
+
+struct App {
+    seed: Option<u64>,
+    bindings: KeyBindings,
+    game: Game,
+    state: State,
+    // プレイヤーごとに次のフレームまでに押されたコマンド。押された順
+    commands: Vec<Vec<Command>>,
+    is_quit: bool,
+}
+
+impl App {
+    fn new(options: &Options, config: GameConfig, bindings: KeyBindings) -> Self {
+        let game = Game::with_config(config, options.game.seed.unwrap_or_else(time_seed));
+        App {
+            seed: options.game.seed,
+            bindings,
+            commands: vec![Vec::new(); game.players.len()],
+            game,
+            state: State::Playing,
+            is_quit: false,
+        }
+    }
+
+    fn start(&mut self) {
+        let seed = self.seed.unwrap_or_else(time_seed);
+        self.game = Game::with_config(self.game.config.clone(), seed);
+        self.commands = vec![Vec::new(); self.game.players.len()];
+        self.state = State::Playing;
+    }
+
+    fn pause_if_playing(&mut self) {
+        if let State::Playing = self.state {
+            self.state = State::Paused;
+        }
+    }
+
+    fn run(&mut self, out: &mut impl Write, colors: ColorMode) -> Result<(), String> {
+        let mut view = new_view(&self.game.config)?;
+        let mut prev: Option<Screen> = None;
+        let mut timestep = FixedTimestep::per_second(FPS, MAX_CATCH_UP_TICKS);
+        let mut last = Instant::now();
+        while !self.is_quit {
+            // 次のフレームまで入力を待つ
+            loop {
+                let timeout = timestep.remaining().saturating_sub(last.elapsed());
+                if !event::poll(timeout).map_err(|e| e.to_string())? {
+                    break;
+                }
+                match event::read().map_err(|e| e.to_string())? {
+                    Event::Key(key) => self.on_key(key),
+                    Event::FocusLost => self.pause_if_playing(),
+                    Event::Resize(..) => {
+                        view = new_view(&self.game.config)?;
+                        prev = None;
+                        queue!(out, ResetColor, Clear(ClearType::All))
+                            .map_err(|e| e.to_string())?;
+                    }
+                    _ => {}
+                }
+            }
+            if self.is_quit {
+                break;
+            }
+
+            if view.is_err() {
+                self.pause_if_playing();
+            }
+            let now = Instant::now();
+            for _ in 0..timestep.advance(now - last) {
+                self.update();
+            }
+            last = now;
+            let screen = match &mut view {
+                Ok(view) => view.draw(&self.game, &self.status())?,
+                Err(e) => too_small(e),
+            };
+            write_screen(out, &screen, prev.as_ref(), colors).map_err(|e| e.to_string())?;
+            prev = Some(screen);
+        }
+        Ok(())
+    }
+
+    fn on_key(&mut self, key: KeyEvent) {
+        if key.kind == KeyEventKind::Release {
+            return;
+        }
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.is_quit = true;
+            return;
+        }
+        if let Some(name) = key_name(key.code) {
+            for action in self.bindings.actions(&name) {
+                if self.on_action(action) {
+                    break;
+                }
+            }
+        }
+    }
+
+    // 操作を行ったらtrue
+    fn on_action(&mut self, action: Action) -> bool {
+        let steering = action.steering(self.game.config.is_versus());
+        if let (State::Playing, Some((player, command))) = (&self.state, steering) {
+            self.commands[player].push(command);
+            return true;
+        }
+        match (&self.state, action) {
+            (State::Playing, Action::Pause) => self.state = State::Paused,
+            (State::Paused, Action::Pause) => self.state = State::Playing,
+            (State::GameOver, Action::Restart) => self.start(),
+            (_, Action::Quit) => self.is_quit = true,
+            _ => return false,
+        }
+        true
+    }
+
+    fn update(&mut self) {
+        if let State::Playing = self.state {
+            let empty = vec![Vec::new(); self.game.players.len()];
+            let commands = std::mem::replace(&mut self.commands, empty);
+            self.game.update_rounds(&command_rounds(&commands));
+            if self.game.is_over {
+                self.state = State::GameOver;
+            }
+        }
+        // 音は出せないので捨てる
+        self.game.take_sounds();
+    }
+
+    // 盤面の下に出すメッセージ
+    fn status(&self) -> String {
+        let pause = self.key_hint(Action::Pause);
+        let restart = self.key_hint(Action::Restart);
+        let quit = self.key_hint(Action::Quit);
+        match self.state {
+            State::Playing => format!("{}: PAUSE  {}: QUIT", pause, quit),
+            State::Paused => format!("PAUSED  {}: RESUME  {}: QUIT", pause, quit),
+            State::GameOver => {
+                let title = if self.game.is_cleared {
+                    "CLEAR!".to_string()
+                } else if self.game.config.is_versus() {
+                    match self.game.winner {
+                        Some(i) => format!("{}P WINS!", i + 1),
+                        None => "DRAW".to_string(),
+                    }
+                } else {
+                    "GAME OVER".to_string()
+                };
+                format!("{}  {}: RESTART  {}: QUIT", title, restart, quit)
+            }
+        }
+    }
+
+    // 最初に割り当てたキーを使う
+    fn key_hint(&self, action: Action) -> String {
+        match self.bindings.keys(action).first() {
+            Some(key) => key.to_uppercase(),
+            None => "-".to_string(),
+        }
+    }
+}
+
+fn new_view(config: &GameConfig) -> Result<Result<TerminalView, String>, String> {
+    let (columns, rows) = terminal::size().map_err(|e| e.to_string())?;
+    Ok(TerminalView::new(config, columns as i32, rows as i32))
+}
+
+// 盤面が入らないときは理由だけ出す
+fn too_small(message: &str) -> Screen {
+    let (columns, rows) = terminal::size().unwrap_or((80, 24));
+    let mut screen = Screen::new(columns as i32, rows as i32);
+    screen.put_text(0, 0, message, Color::rgb(255, 255, 255), BLACK);
+    screen
+}
+
+// 変わったところだけ書く。SSH越しでも重くならないように
+fn write_screen(
+    out: &mut impl Write,
+    screen: &Screen,
+    prev: Option<&Screen>,
+    colors: ColorMode,
+) -> io::Result<()> {
+    let mut current = None;
+    for span in screen.changes(prev) {
+        queue!(out, MoveTo(span.x as u16, span.y as u16))?;
+        for cell in &span.cells {
+            if current != Some((cell.fg, cell.bg)) {
+                let pair = Colors::new(colors.convert(cell.fg), colors.convert(cell.bg));
+                queue!(out, SetColors(pair))?;
+                current = Some((cell.fg, cell.bg));
+            }
+            queue!(out, Print(cell.ch))?;
+        }
+    }
+    out.flush()
+}
+
+// キーの割り当てはSDL版と同じ名前で書くので、端末のキーをSDLのキー名にする
+fn key_name(code: KeyCode) -> Option<String> {
+    let name = match code {
+        KeyCode::Up => "Up",
+        KeyCode::Down => "Down",
+        KeyCode::Left => "Left",
+        KeyCode::Right => "Right",
+        KeyCode::Enter => "Return",
+        KeyCode::Esc => "Escape",
+        KeyCode::Tab => "Tab",
+        KeyCode::Backspace => "Backspace",
+        KeyCode::Char(' ') => "Space",
+        KeyCode::Char(c) => return Some(c.to_uppercase().to_string()),
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+#[derive(Default)]
+struct Options {
+    game: GameOptions,
+    colors: Option<ColorMode>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options::default();
+    for (name, value) in cli::split_args(std::env::args().skip(1))? {
+        match name.as_str() {
+            "--colors" => {
+                options.colors = Some(
+                    ColorMode::from_name(&value)
+                        .ok_or_else(|| format!("invalid colors {:?}", value))?,
+                );
+            }
+            // 端末では1マスが1文字なので大きさは変えられない
+            "--cell-size" => return Err(cli::unknown_argument(&name)),
+            _ if options.game.parse(&name, &value)? => {}
+            _ => return Err(cli::unknown_argument(&name)),
+        }
+    }
+    Ok(options)
+}
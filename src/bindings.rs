@@ -0,0 +1,371 @@
+use crate::model::Command;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Pause,
+    Restart,
+    Quit,
+    Mute,
+    Select,
+    HighScores,
+    Step,
+    FastForward,
+    // 対戦のときの1P・2Pの操作
+    P1Up,
+    P1Down,
+    P1Left,
+    P1Right,
+    P2Up,
+    P2Down,
+    P2Left,
+    P2Right,
+}
+
+impl Action {
+    pub fn all() -> Vec<Self> {
+        vec![
+            Action::Up,
+            Action::Down,
+            Action::Left,
+            Action::Right,
+            Action::Pause,
+            Action::Restart,
+            Action::Quit,
+            Action::Mute,
+            Action::Select,
+            Action::HighScores,
+            Action::Step,
+            Action::FastForward,
+            Action::P1Up,
+            Action::P1Down,
+            Action::P1Left,
+            Action::P1Right,
+            Action::P2Up,
+            Action::P2Down,
+            Action::P2Left,
+            Action::P2Right,
+        ]
+    }
+
+    // 設定ファイルでの名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Pause => "pause",
+            Action::Restart => "restart",
+            Action::Quit => "quit",
+            Action::Mute => "mute",
+            Action::Select => "select",
+            Action::HighScores => "high_scores",
+            Action::Step => "step",
+            Action::FastForward => "fast_forward",
+            Action::P1Up => "p1_up",
+            Action::P1Down => "p1_down",
+            Action::P1Left => "p1_left",
+            Action::P1Right => "p1_right",
+            Action::P2Up => "p2_up",
+            Action::P2Down => "p2_down",
+            Action::P2Left => "p2_left",
+            Action::P2Right => "p2_right",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().into_iter().find(|x| x.name() == name)
+    }
+
+    // ヘビを動かす操作なら (プレイヤーの番号, 向き)。対戦では1P・2P用の操作だけを使う
+    pub fn steering(&self, is_versus: bool) -> Option<(usize, Command)> {
+        let steering = match self {
+            Action::Up if !is_versus => (0, Command::Up),
+            Action::Down if !is_versus => (0, Command::Down),
+            Action::Left if !is_versus => (0, Command::Left),
+            Action::Right if !is_versus => (0, Command::Right),
+            Action::P1Up if is_versus => (0, Command::Up),
+            Action::P1Down if is_versus => (0, Command::Down),
+            Action::P1Left if is_versus => (0, Command::Left),
+            Action::P1Right if is_versus => (0, Command::Right),
+            Action::P2Up if is_versus => (1, Command::Up),
+            Action::P2Down if is_versus => (1, Command::Down),
+            Action::P2Left if is_versus => (1, Command::Left),
+            Action::P2Right if is_versus => (1, Command::Right),
+            _ => return None,
+        };
+        Some(steering)
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Up => "Move player up",
+            Action::Down => "Move player down",
+            Action::Left => "Move player left",
+            Action::Right => "Move player right",
+            Action::Pause => "Pause / resume",
+            Action::Restart => "Restart",
+            Action::Quit => "Back to title, quit on title",
+            Action::Mute => "Mute / unmute sounds",
+            Action::Select => "Select menu item",
+            Action::HighScores => "Show high scores on title or game over",
+            Action::Step => "Step one frame while replay is paused",
+            Action::FastForward => "Toggle replay fast-forward",
+            Action::P1Up => "Move player 1 up in versus",
+            Action::P1Down => "Move player 1 down in versus",
+            Action::P1Left => "Move player 1 left in versus",
+            Action::P1Right => "Move player 1 right in versus",
+            Action::P2Up => "Move player 2 up in versus",
+            Action::P2Down => "Move player 2 down in versus",
+            Action::P2Left => "Move player 2 left in versus",
+            Action::P2Right => "Move player 2 right in versus",
+        }
+    }
+
+    // キーの名前はSDLのKeycodeの名前
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::Up => &["Up", "W", "K"],
+            Action::Down => &["Down", "S", "J"],
+            Action::Left => &["Left", "A", "H"],
+            Action::Right => &["Right", "D", "L"],
+            Action::Pause => &["P", "Return", "Keypad Enter"],
+            Action::Restart => &["Space", "R"],
+            Action::Quit => &["Escape", "Q"],
+            Action::Mute => &["M"],
+            Action::Select => &["Return", "Keypad Enter", "Space"],
+            Action::HighScores => &["Tab"],
+            Action::Step => &["N"],
+            Action::FastForward => &["F"],
+            Action::P1Up => &["W"],
+            Action::P1Down => &["S"],
+            Action::P1Left => &["A"],
+            Action::P1Right => &["D"],
+            Action::P2Up => &["Up"],
+            Action::P2Down => &["Down"],
+            Action::P2Left => &["Left"],
+            Action::P2Right => &["Right"],
+        }
+    }
+}
+
+// 操作ごとに割り当てたキー。1つの操作に複数のキーを割り当てられる
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyBindings {
+    bindings: Vec<(Action, Vec<String>)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            bindings: Action::all()
+                .into_iter()
+                .map(|action| {
+                    let keys = action.default_keys().iter().map(|x| x.to_string());
+                    (action, keys.collect())
+                })
+                .collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+    // ファイルがなければ既定の割り当てを返す
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("cannot read {}: {}", path.display(), e)),
+        }
+    }
+
+    // TOMLの一部だけを読む。書いていない操作は既定のまま
+    //
+    //   [keys]
+    //   up = ["Up", "W", "K"]
+    //   mute = "M"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut bindings = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() || line == "[keys]" {
+                continue;
+            }
+            let invalid = || format!("line {}: invalid entry {:?}", i + 1, line);
+            let (name, value) = line.split_once('=').ok_or_else(invalid)?;
+            let action = Action::from_name(name.trim())
+                .ok_or_else(|| format!("line {}: unknown action {:?}", i + 1, name.trim()))?;
+            let value = value.trim();
+            let keys = match value.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+                Some(list) => list
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|x| !x.is_empty())
+                    .map(|x| parse_string(x).ok_or_else(invalid))
+                    .collect::<Result<Vec<_>, _>>()?,
+                None => vec![parse_string(value).ok_or_else(invalid)?],
+            };
+            bindings.set(action, keys);
+        }
+        Ok(bindings)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = "[keys]\n".to_string();
+        for (action, keys) in &self.bindings {
+            let keys: Vec<String> = keys.iter().map(|x| format!("{:?}", x)).collect();
+            text += &format!("{} = [{}]\n", action.name(), keys.join(", "));
+        }
+        text
+    }
+
+    pub fn set(&mut self, action: Action, keys: Vec<String>) {
+        if let Some(entry) = self.bindings.iter_mut().find(|(x, _)| *x == action) {
+            entry.1 = keys;
+        }
+    }
+
+    pub fn keys(&self, action: Action) -> &[String] {
+        self.bindings
+            .iter()
+            .find(|(x, _)| *x == action)
+            .map_or(&[], |(_, keys)| keys.as_slice())
+    }
+
+    pub fn all_keys(&self) -> impl Iterator<Item = &String> {
+        self.bindings.iter().flat_map(|(_, keys)| keys)
+    }
+
+    // そのキーに割り当てた操作。大文字と小文字は区別しない
+    pub fn actions(&self, key: &str) -> Vec<Action> {
+        self.bindings
+            .iter()
+            .filter(|(_, keys)| keys.iter().any(|x| x.eq_ignore_ascii_case(key)))
+            .map(|(action, _)| *action)
+            .collect()
+    }
+
+    // 起動時に表示する操作説明
+    pub fn help(&self) -> Vec<String> {
+        let rows: Vec<(String, &str)> = self
+            .bindings
+            .iter()
+            .filter(|(_, keys)| !keys.is_empty())
+            .map(|(action, keys)| (keys.join(" / "), action.description()))
+            .collect();
+        let width = rows.iter().map(|(keys, _)| keys.len()).max().unwrap_or(0);
+        rows.into_iter()
+            .map(|(keys, description)| format!("    {:<width$} : {}", keys, description))
+            .collect()
+    }
+}
+
+// ユーザーごとの設定ディレクトリにあるキー設定ファイル
+pub fn default_path() -> Option<PathBuf> {
+    let dir = if cfg!(windows) {
+        PathBuf::from(env::var_os("APPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(env::var_os("HOME")?).join("Library/Application Support")
+    } else {
+        match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        }
+    };
+    Some(dir.join("rust-snake").join("keys.toml"))
+}
+
+// 文字列の外にある # 以降を除く
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_string(s: &str) -> Option<String> {
+    let s = s.strip_prefix('"')?.strip_suffix('"')?;
+    if s.is_empty() || s.contains('"') {
+        return None;
+    }
+    Some(s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_include_arrows_wasd_and_vi_keys() {
+        let bindings = KeyBindings::default();
+        assert_eq!(bindings.actions("K"), vec![Action::Up]);
+        assert_eq!(bindings.actions("w"), vec![Action::Up, Action::P1Up]);
+        assert_eq!(bindings.actions("Up"), vec![Action::Up, Action::P2Up]);
+        assert_eq!(bindings.actions("h"), vec![Action::Left]);
+        assert_eq!(
+            bindings.actions("Return"),
+            vec![Action::Pause, Action::Select]
+        );
+        assert!(bindings.actions("X").is_empty());
+        assert_eq!(bindings.actions("escape"), vec![Action::Quit]);
+    }
+
+    #[test]
+    fn versus_steers_each_player_separately() {
+        assert_eq!(Action::Up.steering(false), Some((0, Command::Up)));
+        assert_eq!(Action::Up.steering(true), None);
+        assert_eq!(Action::P2Left.steering(true), Some((1, Command::Left)));
+        assert_eq!(Action::P2Left.steering(false), None);
+        assert_eq!(Action::Pause.steering(false), None);
+    }
+
+    #[test]
+    fn parse_overrides_only_listed_actions() {
+        let bindings = KeyBindings::parse(
+            "# my keys\n[keys]\nup = [\"I\", \"Up\"] # vi is hard\nmute = \"#\"\npause = []\n",
+        )
+        .unwrap();
+        assert_eq!(bindings.keys(Action::Up), ["I", "Up"]);
+        assert_eq!(bindings.keys(Action::Mute), ["#"]);
+        assert!(bindings.keys(Action::Pause).is_empty());
+        assert_eq!(bindings.keys(Action::Down), ["Down", "S", "J"]);
+    }
+
+    #[test]
+    fn parse_rejects_broken_files() {
+        assert!(KeyBindings::parse("jump = [\"Space\"]").is_err());
+        assert!(KeyBindings::parse("up = Space").is_err());
+        assert!(KeyBindings::parse("up [\"Space\"]").is_err());
+        assert!(KeyBindings::parse("up = [\"Space\", W]").is_err());
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let mut bindings = KeyBindings::default();
+        bindings.set(Action::Restart, vec!["Backspace".to_string()]);
+        assert_eq!(KeyBindings::parse(&bindings.to_text()).unwrap(), bindings);
+    }
+
+    #[test]
+    fn help_lists_bound_keys() {
+        let mut bindings = KeyBindings::default();
+        bindings.set(Action::Step, Vec::new());
+        let help = bindings.help();
+        assert_eq!(help.len(), Action::all().len() - 1);
+        assert!(help[0].starts_with("    Up / W / K "));
+        assert!(help[0].ends_with(" : Move player up"));
+        assert!(help.iter().all(|x| x.find(" : ") == help[0].find(" : ")));
+    }
+}
@@ -0,0 +1,520 @@
+use crate::model::{Boundary, Command, Direction, FoodColor, Game, GameConfig, Point};
+use std::collections::VecDeque;
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Right,
+    Direction::Down,
+    Direction::Left,
+];
+
+// コンピューターが操作するヘビの考え方
+pub trait Strategy {
+    // index番目のヘビの次のコマンドを決める。gameは読むだけで変えない
+    fn command(&mut self, game: &Game, index: usize) -> Command;
+}
+
+// 移動するフレームだけstrategyに聞き、それ以外のフレームはCommand::None
+// 考えるのは今の向きからなので、毎フレーム聞くと同じ曲がり方が溜まって考えた通りに動かない
+pub fn command_on_move(strategy: &mut dyn Strategy, game: &Game, index: usize) -> Command {
+    let player = &game.players[index];
+    if player.is_dead || game.frame != player.next_move_frame {
+        Command::None
+    } else {
+        strategy.command(game, index)
+    }
+}
+
+// コンピューターの強さ
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BotLevel {
+    // 一番近い食べ物にまっすぐ向かう
+    Easy,
+    // 障害物をよけて最短経路で食べ物に向かう
+    Normal,
+    // さらに行き止まりと他のヘビの頭の隣を避ける
+    Hard,
+}
+
+impl BotLevel {
+    pub fn all() -> Vec<Self> {
+        vec![BotLevel::Easy, BotLevel::Normal, BotLevel::Hard]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BotLevel::Easy => "easy",
+            BotLevel::Normal => "normal",
+            BotLevel::Hard => "hard",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().into_iter().find(|x| x.name() == name)
+    }
+
+    pub fn strategy(&self) -> Box<dyn Strategy> {
+        match self {
+            BotLevel::Easy => Box::new(Greedy),
+            BotLevel::Normal => Box::new(Pathfinder { is_careful: false }),
+            BotLevel::Hard => Box::new(Pathfinder { is_careful: true }),
+        }
+    }
+}
+
+// 今いる場所から一番近い食べ物に近づく方へ曲がる。先のことは考えない
+pub struct Greedy;
+
+impl Strategy for Greedy {
+    fn command(&mut self, game: &Game, index: usize) -> Command {
+        let player = &game.players[index];
+        let targets = targets(game);
+        let moves = safe_moves(game, index);
+        // 近づける方向がなければまっすぐ進む
+        let best = moves.iter().min_by_key(|(direction, p)| {
+            let distance = targets
+                .iter()
+                .map(|x| distance(&game.config, p, x))
+                .min()
+                .unwrap_or(0);
+            (distance, *direction != player.direction)
+        });
+        match best {
+            Some((direction, _)) => turn(player.direction, *direction),
+            None => Command::None,
+        }
+    }
+}
+
+// 幅優先探索で障害物をよけながら一番近い食べ物へ向かう
+pub struct Pathfinder {
+    // 体が入りきらない行き止まりと、他のヘビの頭の隣に入らない
+    pub is_careful: bool,
+}
+
+impl Strategy for Pathfinder {
+    fn command(&mut self, game: &Game, index: usize) -> Command {
+        let player = &game.players[index];
+        let blocked = blocked_cells(game);
+        let moves = candidate_moves(game, index, &blocked, self.is_careful);
+        // 一番近い食べ物に最初に着いた1歩を選ぶ
+        match search(game, &blocked, &moves).first() {
+            Some(route) => turn(player.direction, route.first_step),
+            None => escape(game, index, &blocked, &moves),
+        }
+    }
+}
+
+// 腹が減ったとみなす満腹度。これより下では満腹度の回復を優先する
+pub const HUNGRY_ENERGY: i32 = 40;
+// これより体が短いうちは白い食べ物で縮まないようによける
+pub const SHORT_LENGTH: usize = 10;
+
+// デモで自分のヘビを動かす。満腹度を考えて食べ物を選ぶ
+pub struct Autopilot;
+
+impl Strategy for Autopilot {
+    fn command(&mut self, game: &Game, index: usize) -> Command {
+        let player = &game.players[index];
+        let mut blocked = blocked_cells(game);
+        if player.bodies.len() < SHORT_LENGTH {
+            for food in game.existing_foods() {
+                if food.color == FoodColor::White {
+                    blocked[cell(&game.config, &food.p)] = true;
+                }
+            }
+        }
+        let moves = candidate_moves(game, index, &blocked, true);
+        // 着く前に満腹度が尽きる食べ物はあきらめる。腹が減っていれば
+        // 食べたあとの満腹度が一番多くなるもの、そうでなければ一番近いものを選ぶ
+        let is_hungry = player.energy < HUNGRY_ENERGY;
+        let best = search(game, &blocked, &moves)
+            .into_iter()
+            .filter(|x| x.distance <= player.energy)
+            .max_by_key(|x| {
+                let rest = player.energy - x.distance + x.color.energy();
+                if is_hungry {
+                    (rest, -x.distance)
+                } else {
+                    (-x.distance, rest)
+                }
+            });
+        match best {
+            Some(route) => turn(player.direction, route.first_step),
+            None => escape(game, index, &blocked, &moves),
+        }
+    }
+}
+
+// 慎重に動くときは、体が入りきらない行き止まりと他のヘビの頭の隣を除く
+fn candidate_moves(
+    game: &Game,
+    index: usize,
+    blocked: &[bool],
+    is_careful: bool,
+) -> Vec<(Direction, Point)> {
+    let length = game.players[index].bodies.len() + 1;
+    safe_moves(game, index)
+        .into_iter()
+        .filter(|(_, p)| !blocked[cell(&game.config, p)])
+        .filter(|(_, p)| {
+            !is_careful
+                || (!near_other_head(game, index, p)
+                    && space(&game.config, blocked, p, length) >= length)
+        })
+        .collect()
+}
+
+// 食べ物までの道のり
+struct Route {
+    first_step: Direction,
+    // 着くまでの移動回数
+    distance: i32,
+    color: FoodColor,
+}
+
+// 最初の1歩ごとに幅優先探索を広げ、着ける食べ物を近い順に返す
+fn search(game: &Game, blocked: &[bool], moves: &[(Direction, Point)]) -> Vec<Route> {
+    let config = &game.config;
+    let mut colors: Vec<Option<FoodColor>> = vec![None; blocked.len()];
+    for food in game.existing_foods() {
+        if food.color != FoodColor::White {
+            colors[cell(config, &food.p)] = Some(food.color.clone());
+        }
+    }
+    let mut first_steps: Vec<Option<(Direction, i32)>> = vec![None; blocked.len()];
+    let mut queue = VecDeque::new();
+    for (direction, p) in moves {
+        first_steps[cell(config, p)] = Some((*direction, 1));
+        queue.push_back(p.clone());
+    }
+    let mut routes = Vec::new();
+    while let Some(p) = queue.pop_front() {
+        let i = cell(config, &p);
+        let Some((first_step, distance)) = first_steps[i] else {
+            continue;
+        };
+        if let Some(color) = colors[i].take() {
+            routes.push(Route {
+                first_step,
+                distance,
+                color,
+            });
+        }
+        for direction in DIRECTIONS {
+            if let Some(next) = p.neighbor(direction, config) {
+                let j = cell(config, &next);
+                if !blocked[j] && first_steps[j].is_none() {
+                    first_steps[j] = Some((first_step, distance + 1));
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    routes
+}
+
+// 食べ物に行けなければ一番広い方へ逃げる
+fn escape(game: &Game, index: usize, blocked: &[bool], moves: &[(Direction, Point)]) -> Command {
+    let player = &game.players[index];
+    let best = moves.iter().max_by_key(|(direction, p)| {
+        (
+            space(&game.config, blocked, p, blocked.len()),
+            *direction == player.direction,
+        )
+    });
+    match best {
+        Some((direction, _)) => turn(player.direction, *direction),
+        None => fallback(game, index),
+    }
+}
+
+// 慎重に選べる方向がないときは、ぶつからない方向ならどれでもよい
+fn fallback(game: &Game, index: usize) -> Command {
+    let player = &game.players[index];
+    match safe_moves(game, index).first() {
+        Some((direction, _)) => turn(player.direction, *direction),
+        None => Command::None,
+    }
+}
+
+// 向きを変える必要があるときだけコマンドを出す
+fn turn(from: Direction, to: Direction) -> Command {
+    if from == to {
+        return Command::None;
+    }
+    match to {
+        Direction::Left => Command::Left,
+        Direction::Right => Command::Right,
+        Direction::Down => Command::Down,
+        Direction::Up => Command::Up,
+    }
+}
+
+fn cell(config: &GameConfig, p: &Point) -> usize {
+    (p.y * config.width + p.x) as usize
+}
+
+// 端の扱いを考えたマンハッタン距離
+fn distance(config: &GameConfig, a: &Point, b: &Point) -> i32 {
+    let dx = (a.x - b.x).abs();
+    let dy = (a.y - b.y).abs();
+    match config.boundary {
+        Boundary::Wrap => dx.min(config.width - dx) + dy.min(config.height - dy),
+        Boundary::Walls => dx + dy,
+    }
+}
+
+// 狙う食べ物。白は縮むだけなので狙わない
+fn targets(game: &Game) -> Vec<Point> {
+    game.existing_foods()
+        .filter(|x| x.color != FoodColor::White)
+        .map(|x| x.p.clone())
+        .collect()
+}
+
+// 入るとぶつかるマス。ステージの壁・うんこ・生きているヘビ
+fn blocked_cells(game: &Game) -> Vec<bool> {
+    let config = &game.config;
+    let mut blocked = vec![false; (config.width * config.height) as usize];
+    if let Some(level) = &config.level {
+        for p in &level.walls {
+            blocked[cell(config, p)] = true;
+        }
+    }
+    for poo in game.existing_poos() {
+        blocked[cell(config, &poo.p)] = true;
+    }
+    for player in game.players.iter().filter(|x| !x.is_dead) {
+        blocked[cell(config, &player.p)] = true;
+        for p in &player.bodies {
+            blocked[cell(config, p)] = true;
+        }
+    }
+    blocked
+}
+
+// 次の移動ですぐにはぶつからない方向と、その先のマス
+fn safe_moves(game: &Game, index: usize) -> Vec<(Direction, Point)> {
+    let blocked = blocked_cells(game);
+    let player = &game.players[index];
+    DIRECTIONS
+        .into_iter()
+        .filter(|x| player.bodies.is_empty() || *x != player.direction.opposite())
+        .filter_map(|x| Some((x, player.p.neighbor(x, &game.config)?)))
+        .filter(|(_, p)| !blocked[cell(&game.config, p)])
+        .collect()
+}
+
+// 他のヘビが次に頭を出せるマス。入ると正面衝突するかもしれない
+fn near_other_head(game: &Game, index: usize, p: &Point) -> bool {
+    game.players
+        .iter()
+        .enumerate()
+        .filter(|(i, x)| *i != index && !x.is_dead)
+        .any(|(_, x)| distance(&game.config, &x.p, p) == 1)
+}
+
+// pから行けるマスの数。limitまで数えたらやめる
+fn space(config: &GameConfig, blocked: &[bool], p: &Point, limit: usize) -> usize {
+    let mut visited = blocked.to_vec();
+    visited[cell(config, p)] = true;
+    let mut queue = VecDeque::from([p.clone()]);
+    let mut count = 0;
+    while let Some(p) = queue.pop_front() {
+        count += 1;
+        if count >= limit {
+            break;
+        }
+        for direction in DIRECTIONS {
+            if let Some(next) = p.neighbor(direction, config) {
+                let i = cell(config, &next);
+                if !visited[i] {
+                    visited[i] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{GameConfig, Poo, ENERGY_MAX};
+
+    // 人1人とコンピューター1匹。コンピューターは(5, 5)で右を向いている
+    fn game_with_bot(config: GameConfig) -> Game {
+        let mut game = Game::with_config(GameConfig { bots: 1, ..config }, 0);
+        game.players[1].p = Point::new(5, 5);
+        game.players[1].direction = Direction::Right;
+        game
+    }
+
+    fn place(game: &mut Game, food: &[Point], poos: &[Point]) {
+        for p in food {
+            let i = cell(&game.config, p);
+            game.foods[i].is_exist = true;
+            game.foods[i].color = FoodColor::Red;
+        }
+        for (poo, p) in game.poos.iter_mut().zip(poos) {
+            *poo = Poo {
+                p: p.clone(),
+                is_exist: true,
+            };
+        }
+    }
+
+    #[test]
+    fn autopilot_never_leaves_turns_queued() {
+        let mut game = Game::with_seed(3);
+        while !game.is_over && game.frame < 3000 {
+            let command = command_on_move(&mut Autopilot, &game, 0);
+            game.update(command);
+            game.take_sounds();
+            assert!(game.players[0].turn_queue.len() <= 1);
+        }
+        assert!(game.frame > 300);
+    }
+
+    #[test]
+    fn pathfinder_goes_around_poo() {
+        let mut game = game_with_bot(GameConfig::default());
+        place(&mut game, &[Point::new(8, 5)], &[Point::new(6, 5)]);
+        let command = BotLevel::Normal.strategy().command(&game, 1);
+        assert!(
+            matches!(command, Command::Up | Command::Down),
+            "{:?}",
+            command
+        );
+    }
+
+    #[test]
+    fn bots_use_wrap_around() {
+        for level in BotLevel::all() {
+            let mut game = game_with_bot(GameConfig::default());
+            game.players[1].p = Point::new(0, 5);
+            place(&mut game, &[Point::new(20, 5)], &[]);
+            assert_eq!(level.strategy().command(&game, 1), Command::Left);
+
+            let config = GameConfig {
+                boundary: Boundary::Walls,
+                ..GameConfig::default()
+            };
+            let mut game = game_with_bot(config);
+            game.players[1].p = Point::new(0, 5);
+            place(&mut game, &[Point::new(20, 5)], &[]);
+            assert_eq!(level.strategy().command(&game, 1), Command::None);
+        }
+    }
+
+    #[test]
+    fn hard_bot_avoids_dead_ends() {
+        let mut game = game_with_bot(GameConfig::default());
+        game.players[1].bodies = (1..5).rev().map(|x| Point::new(x, 5)).collect();
+        // (5, 4)は3方向をうんこに囲まれた行き止まり
+        place(
+            &mut game,
+            &[Point::new(5, 4)],
+            &[Point::new(4, 4), Point::new(6, 4), Point::new(5, 3)],
+        );
+        assert_eq!(BotLevel::Normal.strategy().command(&game, 1), Command::Up);
+        assert_ne!(BotLevel::Hard.strategy().command(&game, 1), Command::Up);
+    }
+
+    #[test]
+    fn bots_find_food_on_their_own() {
+        for level in BotLevel::all() {
+            let config = GameConfig {
+                bots: 3,
+                bot_level: level,
+                ..GameConfig::default()
+            };
+            let mut game = Game::with_config(config, 1);
+            // 人のプレイヤーは動かさない
+            game.players[0].next_move_frame = i32::MAX;
+            for _ in 0..1500 {
+                game.players[0].energy = 100;
+                game.update(Command::None);
+            }
+            let eaten: i32 = game.players[1..]
+                .iter()
+                .flat_map(|x| x.ate_counts.values())
+                .sum();
+            assert!(eaten > 0, "{}", level.name());
+            assert!(game.take_sounds().iter().all(|x| !x.ends_with("4.wav")));
+        }
+    }
+
+    // 自分のヘビを(5, 5)に右向きで置き、指定した色の食べ物を並べる
+    fn autopilot_game(energy: i32, foods: &[(Point, FoodColor)]) -> Game {
+        let mut game = Game::with_config(GameConfig::default(), 0);
+        game.players[0].p = Point::new(5, 5);
+        game.players[0].direction = Direction::Right;
+        game.players[0].energy = energy;
+        for food in game.foods.iter_mut() {
+            food.is_exist = false;
+        }
+        for (p, color) in foods {
+            let i = cell(&game.config, p);
+            game.foods[i].is_exist = true;
+            game.foods[i].color = color.clone();
+        }
+        game
+    }
+
+    #[test]
+    fn autopilot_goes_for_energy_when_hungry() {
+        let foods = [
+            (Point::new(7, 5), FoodColor::Blue),
+            (Point::new(5, 10), FoodColor::Red),
+        ];
+        let game = autopilot_game(ENERGY_MAX, &foods);
+        assert_eq!(Autopilot.command(&game, 0), Command::None);
+        let game = autopilot_game(10, &foods);
+        assert_eq!(Autopilot.command(&game, 0), Command::Down);
+    }
+
+    #[test]
+    fn autopilot_skips_white_food_when_short() {
+        let foods = [
+            (Point::new(6, 5), FoodColor::White),
+            (Point::new(8, 5), FoodColor::Red),
+        ];
+        let game = autopilot_game(ENERGY_MAX, &foods);
+        let command = Autopilot.command(&game, 0);
+        assert!(
+            matches!(command, Command::Up | Command::Down),
+            "{:?}",
+            command
+        );
+        let mut game = autopilot_game(ENERGY_MAX, &foods);
+        // 下に向かって伸びた長い体
+        game.players[0].bodies = (0..SHORT_LENGTH as i32)
+            .map(|x| Point::new(5, 6 + x))
+            .collect();
+        assert_eq!(Autopilot.command(&game, 0), Command::None);
+    }
+
+    #[test]
+    fn autopilot_keeps_itself_fed() {
+        for seed in 0..5 {
+            let mut game = Game::with_config(GameConfig::default(), seed);
+            while game.frame < 1500 {
+                let command = Autopilot.command(&game, 0);
+                game.update(command);
+            }
+            assert!(!game.is_over, "seed {}", seed);
+            assert!(game.players[0].ate_count >= 20, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn level_names_round_trip() {
+        for level in BotLevel::all() {
+            assert_eq!(BotLevel::from_name(level.name()), Some(level));
+        }
+        assert_eq!(BotLevel::from_name("godlike"), None);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::bindings::{self, KeyBindings};
+use crate::bot::BotLevel;
+use crate::level::Level;
+use crate::model::*;
+use std::path::PathBuf;
+
+// どのプログラムでも同じ意味のコマンドライン引数
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameOptions {
+    pub seed: Option<u64>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub cell_size: Option<i32>,
+    pub boundary: Option<Boundary>,
+    pub level: Option<String>,
+    pub difficulty: Option<Difficulty>,
+    pub players: Option<usize>,
+    pub bots: Option<usize>,
+    pub bot_level: Option<BotLevel>,
+    pub keys: Option<String>,
+}
+
+impl GameOptions {
+    // 共通の引数なら読んでtrueを返す。知らない名前ならfalse
+    pub fn parse(&mut self, name: &str, value: &str) -> Result<bool, String> {
+        match name {
+            "--seed" => {
+                self.seed = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|e| format!("invalid seed {:?}: {}", value, e))?,
+                );
+            }
+            "--width" => self.width = Some(parse_int(name, value)?),
+            "--height" => self.height = Some(parse_int(name, value)?),
+            "--cell-size" => self.cell_size = Some(parse_int(name, value)?),
+            "--boundary" => {
+                self.boundary = Some(
+                    Boundary::from_name(value)
+                        .ok_or_else(|| format!("invalid boundary {:?}", value))?,
+                );
+            }
+            "--level" => self.level = Some(value.to_string()),
+            "--difficulty" => {
+                self.difficulty = Some(
+                    Difficulty::from_name(value)
+                        .ok_or_else(|| format!("invalid difficulty {:?}", value))?,
+                );
+            }
+            "--players" => {
+                self.players = Some(
+                    value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|x| (1..=PLAYERS_MAX).contains(x))
+                        .ok_or_else(|| format!("invalid players {:?}", value))?,
+                );
+            }
+            "--bots" => {
+                self.bots = Some(
+                    value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|x| *x <= BOTS_MAX)
+                        .ok_or_else(|| format!("invalid bots {:?}", value))?,
+                );
+            }
+            "--bot-level" => {
+                self.bot_level = Some(
+                    BotLevel::from_name(value)
+                        .ok_or_else(|| format!("invalid bot level {:?}", value))?,
+                );
+            }
+            "--keys" => self.keys = Some(value.to_string()),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn config(&self) -> Result<GameConfig, String> {
+        let default = GameConfig::default();
+        let cell_size = self.cell_size.unwrap_or(default.cell_size);
+        let config = match &self.level {
+            Some(name) => GameConfig::with_level(load_level(name)?, cell_size)?,
+            None => GameConfig::new(
+                self.width.unwrap_or(default.width),
+                self.height.unwrap_or(default.height),
+                cell_size,
+            )?,
+        };
+        Ok(GameConfig {
+            boundary: self.boundary.unwrap_or(default.boundary),
+            difficulty: self.difficulty.unwrap_or(default.difficulty),
+            players: self.players.unwrap_or(default.players),
+            bots: self.bots.unwrap_or(default.bots),
+            bot_level: self.bot_level.unwrap_or(default.bot_level),
+            ..config
+        })
+    }
+
+    // --keysがなければ設定ディレクトリのkeys.toml、それもなければ既定の割り当て
+    pub fn bindings(&self) -> Result<KeyBindings, String> {
+        let path = match &self.keys {
+            Some(path) => Some(PathBuf::from(path)),
+            None => bindings::default_path(),
+        };
+        match &path {
+            Some(path) => KeyBindings::load(path),
+            None => Ok(KeyBindings::default()),
+        }
+    }
+}
+
+// --name <value> または --name=<value> 形式の引数を名前と値の組にする
+pub fn split_args(args: impl IntoIterator<Item = String>) -> Result<Vec<(String, String)>, String> {
+    let mut pairs = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.split_once('=') {
+            Some((name, value)) => pairs.push((name.to_string(), value.to_string())),
+            None => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("{} requires a value", arg))?;
+                pairs.push((arg, value));
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+pub fn unknown_argument(name: &str) -> String {
+    format!("unknown argument: {}", name)
+}
+
+pub fn parse_int(name: &str, value: &str) -> Result<i32, String> {
+    value
+        .parse::<i32>()
+        .map_err(|e| format!("invalid {} {:?}: {}", name, value, e))
+}
+
+pub fn load_level(name: &str) -> Result<Level, String> {
+    Level::load(name).map_err(|e| {
+        format!(
+            "{}\navailable levels: {}",
+            e,
+            Level::builtin_names().join(", ")
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|x| x.to_string()).collect()
+    }
+
+    fn parse(s: &str) -> Result<GameOptions, String> {
+        let mut options = GameOptions::default();
+        for (name, value) in split_args(args(s))? {
+            if !options.parse(&name, &value)? {
+                return Err(unknown_argument(&name));
+            }
+        }
+        Ok(options)
+    }
+
+    #[test]
+    fn split_args_accepts_both_forms() {
+        assert_eq!(
+            split_args(args("--seed 1 --width=20")).unwrap(),
+            vec![
+                ("--seed".to_string(), "1".to_string()),
+                ("--width".to_string(), "20".to_string())
+            ]
+        );
+        assert!(split_args(args("--seed")).is_err());
+    }
+
+    #[test]
+    fn parse_reads_every_shared_option() {
+        let options = parse(
+            "--seed 7 --width 20 --height=15 --cell-size 16 --boundary wrap --difficulty hard \
+             --players 2 --bots 1 --bot-level easy --keys keys.toml",
+        )
+        .unwrap();
+        assert_eq!(options.seed, Some(7));
+        assert_eq!(options.width, Some(20));
+        assert_eq!(options.height, Some(15));
+        assert_eq!(options.cell_size, Some(16));
+        assert_eq!(options.boundary, Some(Boundary::Wrap));
+        assert_eq!(options.difficulty, Some(Difficulty::Hard));
+        assert_eq!(options.players, Some(2));
+        assert_eq!(options.bots, Some(1));
+        assert_eq!(options.bot_level, Some(BotLevel::Easy));
+        assert_eq!(options.keys.as_deref(), Some("keys.toml"));
+    }
+
+    #[test]
+    fn parse_rejects_bad_values() {
+        assert!(parse("--seed -1").is_err());
+        assert!(parse("--width x").is_err());
+        assert!(parse("--boundary round").is_err());
+        assert!(parse("--difficulty x").is_err());
+        assert!(parse("--players 0").is_err());
+        assert!(parse(&format!("--players {}", PLAYERS_MAX + 1)).is_err());
+        assert!(parse(&format!("--bots {}", BOTS_MAX + 1)).is_err());
+        assert!(parse("--bot-level x").is_err());
+    }
+
+    #[test]
+    fn unknown_names_are_left_to_the_caller() {
+        let mut options = GameOptions::default();
+        assert_eq!(options.parse("--record", "a.txt"), Ok(false));
+        assert_eq!(options, GameOptions::default());
+    }
+
+    #[test]
+    fn config_applies_the_options() {
+        assert_eq!(GameOptions::default().config(), Ok(GameConfig::default()));
+        let config = parse("--width 20 --height 15 --players 2 --boundary wrap")
+            .unwrap()
+            .config()
+            .unwrap();
+        assert_eq!((config.width, config.height), (20, 15));
+        assert_eq!(config.players, 2);
+        assert_eq!(config.boundary, Boundary::Wrap);
+        assert!(parse("--width 1").unwrap().config().is_err());
+        assert!(parse("--level no-such-level").unwrap().config().is_err());
+    }
+}
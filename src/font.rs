@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+
+// 文字と文字の間のドット数
+pub const LETTER_SPACING: i32 = 1;
+// 知らない文字はこれで表示する
+pub const FALLBACK_CHAR: char = '?';
+// アトラスの1行に並べる文字数
+const ATLAS_COLUMNS: i32 = 16;
+
+const BUILTIN_FONT: &str = include_str!("../resources/font/font.txt");
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    // 基準のx座標から描き始めの位置までのずれ
+    pub fn offset(&self, width: i32) -> i32 {
+        match self {
+            Align::Left => 0,
+            Align::Center => -width / 2,
+            Align::Right => -width,
+        }
+    }
+}
+
+// 1文字分のドット。rows[y]の上位ビットが左
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Glyph {
+    pub width: i32,
+    pub rows: Vec<u8>,
+}
+
+impl Glyph {
+    pub fn is_set(&self, x: i32, y: i32) -> bool {
+        self.rows[y as usize] & (1 << (self.width - 1 - x)) != 0
+    }
+}
+
+// アトラスの中での文字の位置。高さはどれもFont::height
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtlasEntry {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+}
+
+// 全部の文字を1枚の画像に並べたもの
+pub struct Atlas {
+    pub width: i32,
+    pub height: i32,
+    // width * height個。trueが点
+    pub pixels: Vec<bool>,
+    pub entries: HashMap<char, AtlasEntry>,
+}
+
+// 文字ごとに幅が違うビットマップフォント
+#[derive(Clone, Debug)]
+pub struct Font {
+    pub height: i32,
+    glyphs: HashMap<char, Glyph>,
+}
+
+impl Font {
+    // resources/font/font.txtを埋め込んだもの
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_FONT).expect("invalid builtin font")
+    }
+
+    // 書式はresources/font/font.txtの先頭を参照
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut glyphs = HashMap::new();
+        let mut height = None;
+        // 読んでいる途中の文字と、その行
+        let mut current: Option<(char, Vec<&str>)> = None;
+
+        for (i, line) in text.lines().chain(std::iter::once("")).enumerate() {
+            let error = |message: &str| format!("line {}: {}", i + 1, message);
+            if line.is_empty() {
+                if let Some((c, rows)) = current.take() {
+                    let glyph = parse_glyph(&rows).map_err(|e| error(&e))?;
+                    let h = *height.get_or_insert(rows.len() as i32);
+                    if rows.len() as i32 != h {
+                        return Err(error(&format!("{:?} must be {} rows high", c, h)));
+                    }
+                    glyphs.insert(c, glyph);
+                }
+                continue;
+            }
+            if let Some((_, rows)) = &mut current {
+                rows.push(line);
+                continue;
+            }
+            if line.starts_with("//") {
+                continue;
+            }
+            if let Some((c, rest)) = line.split_once(" = ") {
+                // 濁点などを重ねて作る文字
+                let (base, mark) = rest
+                    .split_once(" + ")
+                    .ok_or_else(|| error("invalid composition"))?;
+                let c = single_char(c).ok_or_else(|| error("invalid composition"))?;
+                let glyph = match (
+                    single_char(base).and_then(|x| glyphs.get(&x)),
+                    single_char(mark).and_then(|x| glyphs.get(&x)),
+                ) {
+                    (Some(base), Some(mark)) => compose(base, mark),
+                    _ => return Err(error("unknown glyph in composition")),
+                };
+                glyphs.insert(c, glyph);
+                continue;
+            }
+            let c = match line {
+                "space" => ' ',
+                _ => single_char(line).ok_or_else(|| error("invalid glyph name"))?,
+            };
+            if glyphs.contains_key(&c) {
+                return Err(error(&format!("{:?} is defined twice", c)));
+            }
+            current = Some((c, Vec::new()));
+        }
+
+        if !glyphs.contains_key(&FALLBACK_CHAR) {
+            return Err(format!("{:?} is missing", FALLBACK_CHAR));
+        }
+        Ok(Font {
+            height: height.unwrap_or(0),
+            glyphs,
+        })
+    }
+
+    pub fn has(&self, c: char) -> bool {
+        self.glyphs.contains_key(&c)
+    }
+
+    // 知らない文字は FALLBACK_CHAR になる
+    pub fn glyph(&self, c: char) -> (char, &Glyph) {
+        match self.glyphs.get(&c) {
+            Some(glyph) => (c, glyph),
+            None => (FALLBACK_CHAR, &self.glyphs[&FALLBACK_CHAR]),
+        }
+    }
+
+    // 拡大率scaleで描いたときの幅 (ピクセル)
+    pub fn text_width(&self, text: &str, scale: i32) -> i32 {
+        let dots: i32 = text
+            .chars()
+            .map(|c| self.glyph(c).1.width + LETTER_SPACING)
+            .sum();
+        (dots - LETTER_SPACING).max(0) * scale
+    }
+
+    // 1文字ごとの描き始めのx座標 (ドット単位、左端が0)
+    pub fn layout(&self, text: &str) -> Vec<(char, i32)> {
+        let mut x = 0;
+        text.chars()
+            .map(|c| {
+                let (c, glyph) = self.glyph(c);
+                let position = (c, x);
+                x += glyph.width + LETTER_SPACING;
+                position
+            })
+            .collect()
+    }
+
+    pub fn atlas(&self) -> Atlas {
+        let mut chars: Vec<char> = self.glyphs.keys().copied().collect();
+        chars.sort();
+        // 隣の文字がにじまないように1ドットずつ空ける
+        let cell_width = self.glyphs.values().map(|g| g.width).max().unwrap_or(0) + 1;
+        let cell_height = self.height + 1;
+        let rows = (chars.len() as i32 + ATLAS_COLUMNS - 1) / ATLAS_COLUMNS;
+        let width = cell_width * ATLAS_COLUMNS;
+        let height = cell_height * rows;
+
+        let mut pixels = vec![false; (width * height) as usize];
+        let mut entries = HashMap::new();
+        for (i, c) in chars.into_iter().enumerate() {
+            let glyph = &self.glyphs[&c];
+            let entry = AtlasEntry {
+                x: i as i32 % ATLAS_COLUMNS * cell_width,
+                y: i as i32 / ATLAS_COLUMNS * cell_height,
+                width: glyph.width,
+            };
+            for y in 0..self.height {
+                for x in 0..glyph.width {
+                    if glyph.is_set(x, y) {
+                        pixels[((entry.y + y) * width + entry.x + x) as usize] = true;
+                    }
+                }
+            }
+            entries.insert(c, entry);
+        }
+        Atlas {
+            width,
+            height,
+            pixels,
+            entries,
+        }
+    }
+}
+
+fn single_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+fn parse_glyph(rows: &[&str]) -> Result<Glyph, String> {
+    let Some(first) = rows.first() else {
+        return Err("glyph has no rows".to_string());
+    };
+    let width = first.chars().count() as i32;
+    if width > 8 {
+        return Err("glyph is wider than 8 dots".to_string());
+    }
+    let mut bits = Vec::new();
+    for row in rows {
+        if row.chars().count() as i32 != width {
+            return Err(format!("row {:?} has a different width", row));
+        }
+        let mut b = 0u8;
+        for c in row.chars() {
+            b <<= 1;
+            match c {
+                '#' => b |= 1,
+                '.' => {}
+                _ => return Err(format!("unknown dot {:?}", c)),
+            }
+        }
+        bits.push(b);
+    }
+    Ok(Glyph { width, rows: bits })
+}
+
+// markの点を囲む四角をbaseから消してからmarkを重ねる
+fn compose(base: &Glyph, mark: &Glyph) -> Glyph {
+    let width = base.width.max(mark.width);
+    let base_rows = base.rows.iter().map(|row| row << (width - base.width));
+    let mark_rows: Vec<u8> = mark
+        .rows
+        .iter()
+        .map(|row| row << (width - mark.width))
+        .collect();
+
+    let columns = mark_rows.iter().fold(0u8, |acc, row| acc | row);
+    let first = mark_rows.iter().position(|row| *row != 0);
+    let last = mark_rows.iter().rposition(|row| *row != 0);
+    // 一番左の点から一番右の点までのビットを立てる
+    let mask = match columns {
+        0 => 0,
+        _ => (u8::MAX >> columns.leading_zeros()) & (u8::MAX << columns.trailing_zeros()),
+    };
+
+    let rows = base_rows
+        .zip(mark_rows.iter())
+        .enumerate()
+        .map(|(y, (b, m))| match (first, last) {
+            (Some(first), Some(last)) if first <= y && y <= last => (b & !mask) | m,
+            _ => b,
+        })
+        .collect();
+    Glyph { width, rows }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_font_covers_ascii_and_kana() {
+        let font = Font::builtin();
+        assert_eq!(font.height, 7);
+        for c in ' '..='~' {
+            assert!(font.has(c), "{:?}", c);
+            assert_eq!(font.glyph(c).1.width, 5, "{:?}", c);
+        }
+        for c in "あいうえおがぱをんゃっアイウエオガパヲンヴャッー。「」".chars()
+        {
+            assert!(font.has(c), "{:?}", c);
+        }
+    }
+
+    #[test]
+    fn unknown_chars_fall_back() {
+        let font = Font::builtin();
+        assert_eq!(font.glyph('\u{2603}').0, FALLBACK_CHAR);
+        assert_eq!(font.text_width("\u{2603}", 1), font.text_width("?", 1));
+    }
+
+    #[test]
+    fn width_and_layout_follow_glyph_widths() {
+        let font = Font::builtin();
+        assert_eq!(font.text_width("", 2), 0);
+        assert_eq!(font.text_width("AB", 1), 5 + 1 + 5);
+        assert_eq!(font.text_width("Aあ", 3), (5 + 1 + 7) * 3);
+        assert_eq!(font.layout("AあB"), vec![('A', 0), ('あ', 6), ('B', 14)]);
+        assert_eq!(Align::Center.offset(10), -5);
+        assert_eq!(Align::Right.offset(10), -10);
+    }
+
+    #[test]
+    fn composition_replaces_top_right_corner() {
+        let font =
+            Font::parse("?\n#..\n#..\n#..\n\nx\n###\n###\n###\n\nm\n#.#\n...\n...\n\ny = x + m\n")
+                .unwrap();
+        let (_, y) = font.glyph('y');
+        assert_eq!(y.rows, vec![0b101, 0b111, 0b111]);
+    }
+
+    #[test]
+    fn parse_rejects_broken_fonts() {
+        assert!(Font::parse("A\n#.\n.#\n").is_err());
+        assert!(Font::parse("?\n#.\n.\n").is_err());
+        assert!(Font::parse("?\n#.\n.x\n").is_err());
+        assert!(Font::parse("?\n#\n\nAB\n#\n").is_err());
+        assert!(Font::parse("?\n#\n\n?\n#\n").is_err());
+        assert!(Font::parse("?\n#\n#\n\nA\n#\n").is_err());
+        assert!(Font::parse("?\n#\n\nA = B + ?\n").is_err());
+        assert!(Font::parse("?\n").is_err());
+        assert!(Font::parse("?\n#\n\nA\n\n").is_err());
+    }
+
+    #[test]
+    fn atlas_contains_every_glyph() {
+        let font = Font::builtin();
+        let atlas = font.atlas();
+        assert_eq!(atlas.pixels.len(), (atlas.width * atlas.height) as usize);
+        for (c, entry) in &atlas.entries {
+            let (_, glyph) = font.glyph(*c);
+            assert_eq!(entry.width, glyph.width);
+            for y in 0..font.height {
+                for x in 0..glyph.width {
+                    let pixel = atlas.pixels[((entry.y + y) * atlas.width + entry.x + x) as usize];
+                    assert_eq!(pixel, glyph.is_set(x, y), "{:?}", c);
+                }
+            }
+        }
+    }
+}
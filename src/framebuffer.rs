@@ -0,0 +1,451 @@
+use crate::font::Font;
+use crate::model::Direction;
+use crate::render::{head_pattern, Color, Rect, Renderer, PLAYER_COLORS};
+use crate::sprite::{Sprite, SpriteSheet, COLOR_KEY};
+
+const NUMBERS_BMP: &[u8] = include_bytes!("../resources/image/numbers.bmp");
+
+// 画像。pixels[y * width + x]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bitmap {
+    pub width: i32,
+    pub height: i32,
+    pub pixels: Vec<Color>,
+}
+
+impl Bitmap {
+    // 無圧縮で24ビットか32ビットのBMPだけ読める
+    pub fn parse_bmp(bytes: &[u8]) -> Result<Self, String> {
+        let u16_at = |i: usize| {
+            bytes
+                .get(i..i + 2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        };
+        let i32_at = |i: usize| {
+            bytes
+                .get(i..i + 4)
+                .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
+        if bytes.get(0..2) != Some(b"BM") {
+            return Err("not a BMP file".to_string());
+        }
+        let invalid = || "broken BMP header".to_string();
+        let offset = i32_at(10).ok_or_else(invalid)? as usize;
+        let width = i32_at(18).ok_or_else(invalid)?;
+        let height = i32_at(22).ok_or_else(invalid)?;
+        let bits = u16_at(28).ok_or_else(invalid)?;
+        let compression = i32_at(30).ok_or_else(invalid)?;
+        // 32ビットはBI_BITFIELDS (3) でもBGRAの順とみなす
+        let bytes_per_pixel = match (bits, compression) {
+            (24, 0) => 3,
+            (32, 0 | 3) => 4,
+            _ => return Err(format!("unsupported BMP format: {} bits", bits)),
+        };
+        if width <= 0 || height == 0 {
+            return Err("empty BMP".to_string());
+        }
+        // 高さが正なら下の行から並んでいる
+        let rows = height.abs();
+        let stride = (width as usize * bytes_per_pixel).div_ceil(4) * 4;
+        let mut pixels = Vec::with_capacity((width * rows) as usize);
+        for y in 0..rows {
+            let row = if height > 0 { rows - 1 - y } else { y };
+            let start = offset + row as usize * stride;
+            for x in 0..width as usize {
+                let i = start + x * bytes_per_pixel;
+                let b = bytes.get(i..i + 3).ok_or("BMP is too short")?;
+                pixels.push(Color::rgb(b[2], b[1], b[0]));
+            }
+        }
+        Ok(Bitmap {
+            width,
+            height: rows,
+            pixels,
+        })
+    }
+
+    pub fn pixel(&self, x: i32, y: i32) -> Color {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    // 画像ビューアで開けるバイナリのPPM (P6)
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut bytes = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for c in &self.pixels {
+            bytes.extend([c.r, c.g, c.b]);
+        }
+        bytes
+    }
+
+    pub fn parse_ppm(bytes: &[u8]) -> Result<Self, String> {
+        // ヘッダーは空白で区切った4つの値
+        let mut fields = Vec::new();
+        let mut i = 0;
+        while fields.len() < 4 {
+            while bytes.get(i).is_some_and(|x| x.is_ascii_whitespace()) {
+                i += 1;
+            }
+            let start = i;
+            while bytes.get(i).is_some_and(|x| !x.is_ascii_whitespace()) {
+                i += 1;
+            }
+            if start == i {
+                return Err("broken PPM header".to_string());
+            }
+            fields.push(String::from_utf8_lossy(&bytes[start..i]).to_string());
+        }
+        let number = |s: &str| {
+            s.parse::<i32>()
+                .map_err(|_| "broken PPM header".to_string())
+        };
+        if fields[0] != "P6" || fields[3] != "255" {
+            return Err("unsupported PPM format".to_string());
+        }
+        let (width, height) = (number(&fields[1])?, number(&fields[2])?);
+        let data = &bytes[i + 1..];
+        if data.len() != (width * height * 3) as usize {
+            return Err("PPM size does not match".to_string());
+        }
+        let pixels = data
+            .chunks(3)
+            .map(|c| Color::rgb(c[0], c[1], c[2]))
+            .collect();
+        Ok(Bitmap {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+// メモリ上に描く。ウィンドウなしで描いた結果を確かめるのに使う
+pub struct Framebuffer {
+    pub width: i32,
+    pub height: i32,
+    // 不透明な色だけが入る
+    pub pixels: Vec<Color>,
+    font: Font,
+    numbers: Bitmap,
+    clip: Option<Rect>,
+    // 無ければ単色で描く
+    sprites: Option<(Bitmap, SpriteSheet)>,
+}
+
+impl Framebuffer {
+    pub fn new(width: i32, height: i32) -> Self {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Color::rgb(0, 0, 0); (width * height) as usize],
+            font: Font::builtin(),
+            numbers: Bitmap::parse_bmp(NUMBERS_BMP).expect("invalid numbers.bmp"),
+            clip: None,
+            sprites: None,
+        }
+    }
+
+    // 体・食べ物・うんちを絵で描くようにする
+    pub fn set_sprites(&mut self, bitmap: Bitmap) -> Result<(), String> {
+        let sheet = SpriteSheet::new(bitmap.width, bitmap.height)?;
+        self.sprites = Some((bitmap, sheet));
+        Ok(())
+    }
+
+    pub fn pixel(&self, x: i32, y: i32) -> Color {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    // 画面と切り取り範囲の外は描かない
+    pub fn blend(&mut self, x: i32, y: i32, color: Color) {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return;
+        }
+        if self.clip.is_some_and(|clip| !clip.contains(x, y)) {
+            return;
+        }
+        let i = (y * self.width + x) as usize;
+        let dst = self.pixels[i];
+        let a = color.a as u32;
+        let mix = |s: u8, d: u8| ((s as u32 * a + d as u32 * (255 - a)) / 255) as u8;
+        self.pixels[i] = Color::rgb(
+            mix(color.r, dst.r),
+            mix(color.g, dst.g),
+            mix(color.b, dst.b),
+        );
+    }
+
+    // 一部を切り出す
+    pub fn crop(&self, rect: Rect) -> Bitmap {
+        let mut pixels = Vec::with_capacity((rect.w * rect.h).max(0) as usize);
+        for y in rect.y..rect.y + rect.h {
+            for x in rect.x..rect.x + rect.w {
+                pixels.push(self.pixel(x, y));
+            }
+        }
+        Bitmap {
+            width: rect.w,
+            height: rect.h,
+            pixels,
+        }
+    }
+
+    pub fn to_bitmap(&self) -> Bitmap {
+        self.crop(Rect::new(0, 0, self.width, self.height))
+    }
+}
+
+impl Renderer for Framebuffer {
+    fn font(&self) -> &Font {
+        &self.font
+    }
+
+    fn digit_size(&self) -> (i32, i32) {
+        (self.numbers.width / 10, self.numbers.height)
+    }
+
+    fn clear(&mut self, color: Color) -> Result<(), String> {
+        self.pixels.fill(Color { a: 255, ..color });
+        Ok(())
+    }
+
+    fn fill_rect(&mut self, rect: Rect, color: Color) -> Result<(), String> {
+        for y in rect.y..rect.y + rect.h {
+            for x in rect.x..rect.x + rect.w {
+                self.blend(x, y, color);
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_rect(&mut self, rect: Rect, color: Color) -> Result<(), String> {
+        if rect.w <= 0 || rect.h <= 0 {
+            return Ok(());
+        }
+        let (right, bottom) = (rect.x + rect.w - 1, rect.y + rect.h - 1);
+        for x in rect.x..=right {
+            self.blend(x, rect.y, color);
+            if bottom != rect.y {
+                self.blend(x, bottom, color);
+            }
+        }
+        for y in rect.y + 1..bottom {
+            self.blend(rect.x, y, color);
+            if right != rect.x {
+                self.blend(right, y, color);
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_digit(&mut self, digit: u32, x: i32, y: i32) -> Result<(), String> {
+        let (w, h) = self.digit_size();
+        for dy in 0..h {
+            for dx in 0..w {
+                let color = self.numbers.pixel(w * digit as i32 + dx, dy);
+                self.blend(x + dx, y + dy, color);
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_glyph(
+        &mut self,
+        c: char,
+        x: i32,
+        y: i32,
+        scale: i32,
+        color: Color,
+    ) -> Result<(), String> {
+        let glyph = self.font.glyph(c).1.clone();
+        for gy in 0..self.font.height {
+            for gx in 0..glyph.width {
+                if glyph.is_set(gx, gy) {
+                    let dot = Rect::new(x + gx * scale, y + gy * scale, scale, scale);
+                    self.fill_rect(dot, color)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn set_clip(&mut self, rect: Option<Rect>) -> Result<(), String> {
+        self.clip = rect;
+        Ok(())
+    }
+
+    fn draw_head(&mut self, index: usize, direction: Direction, rect: Rect) -> Result<(), String> {
+        let size = rect.w;
+        for (part, color) in head_pattern(size, PLAYER_COLORS[index]) {
+            // マスが小さいと模様がはみ出すので切り取る
+            for y in part.y.max(0)..(part.y + part.h).min(size) {
+                for x in part.x.max(0)..(part.x + part.w).min(size) {
+                    let (rx, ry) = rotate(direction, size, x, y);
+                    self.blend(rect.x + rx, rect.y + ry, color);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn has_sprites(&self) -> bool {
+        self.sprites.is_some()
+    }
+
+    fn draw_sprite(
+        &mut self,
+        sprite: &Sprite,
+        direction: Direction,
+        rect: Rect,
+        tint: Color,
+    ) -> Result<(), String> {
+        let Some((bitmap, sheet)) = &self.sprites else {
+            return Err("sprites are not loaded".to_string());
+        };
+        let tile = sheet.tile(sprite);
+        let size = rect.w;
+        let mut dots = Vec::with_capacity((size * size).max(0) as usize);
+        for y in 0..size {
+            for x in 0..size {
+                // 一番近い画素を使う
+                let color = bitmap.pixel(tile.x + x * tile.w / size, tile.y + y * tile.h / size);
+                if color == COLOR_KEY {
+                    continue;
+                }
+                let (rx, ry) = rotate(direction, size, x, y);
+                dots.push((rect.x + rx, rect.y + ry, multiply(color, tint)));
+            }
+        }
+        for (x, y, color) in dots {
+            self.blend(x, y, color);
+        }
+        Ok(())
+    }
+}
+
+// 上向きの絵の(x, y)を回した位置。SDLと同じく時計回り
+fn rotate(direction: Direction, size: i32, x: i32, y: i32) -> (i32, i32) {
+    match direction {
+        Direction::Up => (x, y),
+        Direction::Right => (size - 1 - y, x),
+        Direction::Down => (size - 1 - x, size - 1 - y),
+        Direction::Left => (y, size - 1 - x),
+    }
+}
+
+// SDLのset_color_modと同じ掛け算
+fn multiply(color: Color, tint: Color) -> Color {
+    let mul = |c: u8, t: u8| (c as u32 * t as u32 / 255) as u8;
+    Color::rgba(
+        mul(color.r, tint.r),
+        mul(color.g, tint.g),
+        mul(color.b, tint.b),
+        tint.a,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_image_has_ten_digits() {
+        let numbers = Bitmap::parse_bmp(NUMBERS_BMP).unwrap();
+        assert_eq!((numbers.width, numbers.height), (80, 16));
+        assert_eq!(Framebuffer::new(1, 1).digit_size(), (8, 16));
+        assert!(Bitmap::parse_bmp(b"BM").is_err());
+        assert!(Bitmap::parse_bmp(b"P6\n1 1\n255\n").is_err());
+    }
+
+    #[test]
+    fn translucent_colors_are_blended() {
+        let mut fb = Framebuffer::new(4, 4);
+        fb.clear(Color::rgb(200, 0, 100)).unwrap();
+        fb.fill_rect(Rect::new(1, 1, 2, 2), Color::rgba(0, 0, 0, 128))
+            .unwrap();
+        assert_eq!(fb.pixel(0, 0), Color::rgb(200, 0, 100));
+        assert_eq!(fb.pixel(1, 1), Color::rgb(99, 0, 49));
+        // 画面の外ははみ出さない
+        fb.fill_rect(Rect::new(3, 3, 5, 5), Color::rgb(1, 2, 3))
+            .unwrap();
+        assert_eq!(fb.pixel(3, 3), Color::rgb(1, 2, 3));
+    }
+
+    #[test]
+    fn draw_rect_draws_only_the_frame() {
+        let mut fb = Framebuffer::new(5, 5);
+        fb.draw_rect(Rect::new(0, 0, 5, 4), WHITE_FOR_TEST).unwrap();
+        assert_eq!(fb.pixel(0, 0), WHITE_FOR_TEST);
+        assert_eq!(fb.pixel(4, 3), WHITE_FOR_TEST);
+        assert_eq!(fb.pixel(2, 2), Color::rgb(0, 0, 0));
+        assert_eq!(fb.pixel(2, 4), Color::rgb(0, 0, 0));
+    }
+
+    const WHITE_FOR_TEST: Color = Color::rgb(255, 255, 255);
+
+    #[test]
+    fn head_turns_with_direction() {
+        let eye = Color::rgb(0, 0, 0);
+        let mut fb = Framebuffer::new(20, 20);
+        let rect = Rect::new(0, 0, 20, 20);
+        fb.draw_head(0, Direction::Up, rect).unwrap();
+        assert_eq!(fb.pixel(8, 0), eye);
+        assert_eq!(fb.pixel(10, 19), PLAYER_COLORS[0]);
+        fb.draw_head(0, Direction::Right, rect).unwrap();
+        assert_eq!(fb.pixel(19, 8), eye);
+        assert_eq!(fb.pixel(8, 0), PLAYER_COLORS[0]);
+        fb.draw_head(0, Direction::Down, rect).unwrap();
+        assert_eq!(fb.pixel(11, 19), eye);
+        fb.draw_head(0, Direction::Left, rect).unwrap();
+        assert_eq!(fb.pixel(0, 11), eye);
+    }
+
+    #[test]
+    fn sprites_are_scaled_rotated_and_tinted() {
+        // 2x2の絵。Straightの左上だけ白で、残りは透明
+        let mut pixels = vec![COLOR_KEY; 16 * 2];
+        pixels[0] = WHITE_FOR_TEST;
+        let sheet = Bitmap {
+            width: 16,
+            height: 2,
+            pixels,
+        };
+        let mut fb = Framebuffer::new(4, 4);
+        assert!(!fb.has_sprites());
+        fb.set_sprites(sheet).unwrap();
+        assert!(fb.has_sprites());
+        let red = Color::rgb(255, 0, 0);
+        fb.draw_sprite(
+            &Sprite::Straight,
+            Direction::Right,
+            Rect::new(0, 0, 4, 4),
+            red,
+        )
+        .unwrap();
+        // 左上の2x2が右上に回る
+        assert_eq!(fb.pixel(2, 0), red);
+        assert_eq!(fb.pixel(3, 1), red);
+        assert_eq!(fb.pixel(0, 0), Color::rgb(0, 0, 0));
+        assert_eq!(fb.pixel(2, 2), Color::rgb(0, 0, 0));
+    }
+
+    #[test]
+    fn bundled_sprite_sheet_is_valid() {
+        let bitmap = Bitmap::parse_bmp(include_bytes!("../resources/image/sprites.bmp")).unwrap();
+        let mut fb = Framebuffer::new(1, 1);
+        fb.set_sprites(bitmap).unwrap();
+        assert!(fb.set_sprites(Framebuffer::new(3, 1).to_bitmap()).is_err());
+    }
+
+    #[test]
+    fn ppm_round_trip() {
+        let mut fb = Framebuffer::new(3, 2);
+        fb.fill_rect(Rect::new(1, 0, 1, 2), Color::rgb(10, 20, 30))
+            .unwrap();
+        let bitmap = fb.to_bitmap();
+        assert_eq!(Bitmap::parse_ppm(&bitmap.to_ppm()).unwrap(), bitmap);
+        assert_eq!(
+            fb.crop(Rect::new(1, 1, 1, 1)).pixels,
+            vec![Color::rgb(10, 20, 30)]
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use crate::model::Direction;
+
+// スティックの値は-32768〜32767。これより小さい傾きは無視する
+pub const STICK_DEADZONE: i32 = 10000;
+// 向きを決めるには傾きの大きい方の軸が小さい方のこの倍以上必要
+pub const STICK_AXIS_RATIO: i32 = 2;
+
+// アナログスティックの傾きを上下左右に直す
+#[derive(Clone, Debug, Default)]
+pub struct Stick {
+    pub x: i32,
+    pub y: i32,
+    direction: Option<Direction>,
+}
+
+impl Stick {
+    pub fn set_x(&mut self, value: i16) -> Option<Direction> {
+        self.x = value as i32;
+        self.update()
+    }
+
+    // SDLでは下が正
+    pub fn set_y(&mut self, value: i16) -> Option<Direction> {
+        self.y = value as i32;
+        self.update()
+    }
+
+    pub fn direction(&self) -> Option<Direction> {
+        self.direction
+    }
+
+    // 新しい向きに倒したときだけその向きを返す
+    fn update(&mut self) -> Option<Direction> {
+        let direction = self.current_direction();
+        if direction == self.direction {
+            return None;
+        }
+        self.direction = direction;
+        direction
+    }
+
+    fn current_direction(&self) -> Option<Direction> {
+        let (ax, ay) = (self.x.abs(), self.y.abs());
+        if ax.max(ay) < STICK_DEADZONE {
+            None
+        } else if ax >= ay * STICK_AXIS_RATIO {
+            Some(if self.x < 0 {
+                Direction::Left
+            } else {
+                Direction::Right
+            })
+        } else if ay >= ax * STICK_AXIS_RATIO {
+            Some(if self.y < 0 {
+                Direction::Up
+            } else {
+                Direction::Down
+            })
+        } else {
+            // 斜めのあたりでは前の向きのままにして、ぶれで曲がらないようにする
+            self.direction
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_tilt_is_ignored() {
+        let mut stick = Stick::default();
+        assert_eq!(stick.set_x(5000), None);
+        assert_eq!(stick.set_y(-5000), None);
+        assert_eq!(stick.direction(), None);
+    }
+
+    #[test]
+    fn tilting_reports_each_new_direction_once() {
+        let mut stick = Stick::default();
+        assert_eq!(stick.set_x(-30000), Some(Direction::Left));
+        assert_eq!(stick.set_x(-32000), None);
+        assert_eq!(stick.set_x(0), None);
+        assert_eq!(stick.set_y(30000), Some(Direction::Down));
+        assert_eq!(stick.set_y(0), None);
+        assert_eq!(stick.direction(), None);
+        assert_eq!(stick.set_y(-20000), Some(Direction::Up));
+    }
+
+    #[test]
+    fn diagonal_wobble_keeps_direction() {
+        let mut stick = Stick::default();
+        assert_eq!(stick.set_x(30000), Some(Direction::Right));
+        // 斜め上に揺れても上には曲がらない
+        assert_eq!(stick.set_y(-20000), None);
+        assert_eq!(stick.set_y(-28000), None);
+        assert_eq!(stick.direction(), Some(Direction::Right));
+        // はっきり上に倒すと曲がる
+        assert_eq!(stick.set_x(8000), Some(Direction::Up));
+    }
+}
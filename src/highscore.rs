@@ -0,0 +1,330 @@
+use crate::model::{FoodColor, Game, GameConfig};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const HIGH_SCORES_MAX: usize = 10;
+pub const NAME_MAX_LEN: usize = 10;
+// 得点の計算を変えたら上げる。前の計算の記録と比べないように記録ファイルを分ける
+pub const SCORE_VERSION: u32 = 2;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HighScore {
+    pub name: String,
+    pub score: i32,
+    pub length: i32,
+    pub ate_counts: HashMap<FoodColor, i32>,
+    pub seed: u64,
+    // UNIX時間 (秒)
+    pub timestamp: u64,
+}
+
+impl HighScore {
+    // 記録するのは1人目のプレイヤー
+    pub fn new(name: &str, game: &Game, timestamp: u64) -> Self {
+        let player = &game.players[0];
+        HighScore {
+            name: sanitize_name(name),
+            score: player.score,
+            length: player.bodies.len() as i32 + 1,
+            ate_counts: player.ate_counts.clone(),
+            seed: game.seed,
+            timestamp,
+        }
+    }
+
+    // YYYY-MM-DD (UTC)
+    pub fn date(&self) -> String {
+        let (y, m, d) = civil_from_days((self.timestamp / 86400) as i64);
+        format!("{:04}-{:02}-{:02}", y, m, d)
+    }
+}
+
+// ゲームモードごとの上位の記録
+#[derive(Default)]
+pub struct HighScores {
+    tables: HashMap<String, Vec<HighScore>>,
+}
+
+impl HighScores {
+    // ファイルがなければ空の表を返す
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("cannot read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+        }
+        fs::write(path, self.to_text())
+            .map_err(|e| format!("cannot write {}: {}", path.display(), e))
+    }
+
+    pub fn top(&self, mode: &str) -> &[HighScore] {
+        self.tables.get(mode).map_or(&[], |x| x.as_slice())
+    }
+
+    pub fn qualifies(&self, mode: &str, score: i32) -> bool {
+        let table = self.top(mode);
+        table.len() < HIGH_SCORES_MAX || table.last().is_some_and(|x| score > x.score)
+    }
+
+    // 順位 (0始まり) を返す。ランク外ならNone
+    pub fn insert(&mut self, mode: &str, entry: HighScore) -> Option<usize> {
+        if !self.qualifies(mode, entry.score) {
+            return None;
+        }
+        let table = self.tables.entry(mode.to_string()).or_default();
+        // 同点なら先に出した人が上
+        let rank = table.iter().take_while(|x| x.score >= entry.score).count();
+        table.insert(rank, entry);
+        table.truncate(HIGH_SCORES_MAX);
+        Some(rank)
+    }
+
+    // 1行1記録のタブ区切り
+    // mode name score length white,red,yellow,blue seed timestamp
+    pub fn to_text(&self) -> String {
+        let mut modes: Vec<&String> = self.tables.keys().collect();
+        modes.sort();
+        let mut text = String::new();
+        for mode in modes {
+            for x in &self.tables[mode] {
+                let counts: Vec<String> = FoodColor::all()
+                    .iter()
+                    .map(|color| x.ate_counts.get(color).copied().unwrap_or(0).to_string())
+                    .collect();
+                text += &format!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                    mode,
+                    x.name,
+                    x.score,
+                    x.length,
+                    counts.join(","),
+                    x.seed,
+                    x.timestamp
+                );
+            }
+        }
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut scores = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || format!("line {}: invalid entry", i + 1);
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 7 {
+                return Err(invalid());
+            }
+            let counts: Vec<i32> = fields[4]
+                .split(',')
+                .map(|x| x.parse::<i32>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid())?;
+            if counts.len() != FoodColor::all().len() {
+                return Err(invalid());
+            }
+            let entry = HighScore {
+                name: fields[1].to_string(),
+                score: fields[2].parse().map_err(|_| invalid())?,
+                length: fields[3].parse().map_err(|_| invalid())?,
+                ate_counts: FoodColor::all().into_iter().zip(counts).collect(),
+                seed: fields[5].parse().map_err(|_| invalid())?,
+                timestamp: fields[6].parse().map_err(|_| invalid())?,
+            };
+            scores.insert(fields[0], entry);
+        }
+        Ok(scores)
+    }
+}
+
+// 難易度・端の扱い・ステージ (または盤面の大きさ)・コンピューターの数と強さが同じものを同じモードとする
+pub fn mode_key(config: &GameConfig) -> String {
+    let board = match &config.level {
+        Some(level) => level.name.clone(),
+        None => format!("{}x{}", config.width, config.height),
+    };
+    let mut key = format!(
+        "{}/{}/{}",
+        config.difficulty.name(),
+        config.boundary.name(),
+        board
+    );
+    if config.bots > 0 {
+        key += &format!("/bots{}-{}", config.bots, config.bot_level.name());
+    }
+    key
+}
+
+// ユーザーごとのデータディレクトリにある記録ファイル
+pub fn default_path() -> Option<PathBuf> {
+    let dir = if cfg!(windows) {
+        PathBuf::from(env::var_os("APPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(env::var_os("HOME")?).join("Library/Application Support")
+    } else {
+        match env::var_os("XDG_DATA_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+        }
+    };
+    Some(dir.join("rust-snake").join(file_name()))
+}
+
+// 最初の版 (遊んだ時間から決まる得点) の記録はhighscores.tsvにある
+pub fn file_name() -> String {
+    format!("highscores-v{}.tsv", SCORE_VERSION)
+}
+
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0)
+}
+
+// 区切り文字や制御文字を除いて長さを揃える
+pub fn sanitize_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(NAME_MAX_LEN)
+        .collect();
+    let name = name.trim();
+    if name.is_empty() {
+        "NONAME".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+// 1970-01-01からの日数を年月日にする
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::BotLevel;
+
+    fn entry(name: &str, score: i32) -> HighScore {
+        let mut game = Game::with_seed(5);
+        game.players[0].score = score;
+        HighScore::new(name, &game, 1_700_000_000)
+    }
+
+    #[test]
+    fn insert_keeps_table_sorted_and_capped() {
+        let mut scores = HighScores::default();
+        for i in 0..HIGH_SCORES_MAX as i32 {
+            assert!(scores.insert("m", entry("A", i * 10)).is_some());
+        }
+        assert!(!scores.qualifies("m", 0));
+        assert!(scores.qualifies("m", 1));
+        assert!(scores.qualifies("other", 0));
+        assert_eq!(scores.insert("m", entry("B", 45)), Some(5));
+        assert_eq!(scores.insert("m", entry("C", 0)), None);
+
+        let top = scores.top("m");
+        assert_eq!(top.len(), HIGH_SCORES_MAX);
+        assert!(top.windows(2).all(|w| w[0].score >= w[1].score));
+        assert_eq!(top[5].name, "B");
+    }
+
+    #[test]
+    fn ties_rank_below_earlier_entries() {
+        let mut scores = HighScores::default();
+        scores.insert("m", entry("FIRST", 10));
+        assert_eq!(scores.insert("m", entry("SECOND", 10)), Some(1));
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let mut scores = HighScores::default();
+        let mut e = entry("AOYAMA", 42);
+        e.ate_counts.insert(FoodColor::Red, 3);
+        scores.insert("normal/wrap/21x20", e.clone());
+        scores.insert("hard/walls/box", entry("X", 7));
+
+        let parsed = HighScores::parse(&scores.to_text()).unwrap();
+        assert_eq!(parsed.top("normal/wrap/21x20"), &[e]);
+        assert_eq!(parsed.top("hard/walls/box").len(), 1);
+        assert!(HighScores::parse("broken line").is_err());
+    }
+
+    #[test]
+    fn names_are_sanitized() {
+        assert_eq!(sanitize_name("a\tb\nc"), "abc");
+        assert_eq!(sanitize_name("   "), "NONAME");
+        assert_eq!(sanitize_name("ABCDEFGHIJKLMNOP").len(), NAME_MAX_LEN);
+    }
+
+    #[test]
+    fn date_is_formatted_in_utc() {
+        assert_eq!(entry("A", 0).date(), "2023-11-14");
+        let mut e = entry("A", 0);
+        e.timestamp = 0;
+        assert_eq!(e.date(), "1970-01-01");
+    }
+
+    #[test]
+    fn scores_of_the_old_formula_are_kept_apart() {
+        let dir = env::temp_dir().join(format!("rust-snake-highscore-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (v1_path, v2_path) = (dir.join("highscores.tsv"), dir.join(file_name()));
+        let mut v1 = HighScores::default();
+        v1.insert("normal", entry("OLD", 9000));
+        v1.save(&v1_path).unwrap();
+        let v1_bytes = fs::read(&v1_path).unwrap();
+
+        let mut v2 = HighScores::load(&v2_path).unwrap();
+        assert!(v2.top("normal").is_empty());
+        v2.insert("normal", entry("NEW", 300));
+        v2.save(&v2_path).unwrap();
+        let loaded = HighScores::load(&v2_path).unwrap();
+        let names: Vec<&str> = loaded
+            .top("normal")
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["NEW"]);
+        assert_eq!(fs::read(&v1_path).unwrap(), v1_bytes);
+        fs::remove_dir_all(&dir).unwrap();
+        if let Some(path) = default_path() {
+            assert!(path.ends_with(file_name()));
+        }
+    }
+
+    #[test]
+    fn mode_key_separates_settings() {
+        let config = GameConfig::default();
+        assert_eq!(mode_key(&config), "normal/wrap/21x20");
+        let config = GameConfig {
+            bots: 2,
+            bot_level: BotLevel::Hard,
+            ..config
+        };
+        assert_eq!(mode_key(&config), "normal/wrap/21x20/bots2-hard");
+    }
+}
@@ -0,0 +1,198 @@
+use crate::model::{Direction, Point};
+use std::fs;
+use std::path::Path;
+
+pub const LEVELS_DIR: &str = "resources/levels";
+
+// テキストで書かれたステージ
+//
+//   title: Box           ヘッダー (省略可)
+//   direction: up        開始時の向き (up/down/left/right)
+//   #####.#####          以降は盤面
+//   #....S....#            # 壁  S 開始位置  * 食べ物が出る場所  . 空き
+//
+// * が1つもなければ食べ物は壁以外のどこにでも出る
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Level {
+    // 読み込むときに指定した名前またはパス
+    pub name: String,
+    pub title: String,
+    pub width: i32,
+    pub height: i32,
+    pub start: Point,
+    pub direction: Direction,
+    pub walls: Vec<Point>,
+    pub food_zones: Vec<Point>,
+}
+
+impl Level {
+    // 名前ならresources/levels/から、パスならそのファイルを読む
+    pub fn load(name_or_path: &str) -> Result<Self, String> {
+        let path = if name_or_path.contains('/') || name_or_path.ends_with(".txt") {
+            name_or_path.to_string()
+        } else {
+            format!("{}/{}.txt", LEVELS_DIR, name_or_path)
+        };
+        let text =
+            fs::read_to_string(&path).map_err(|e| format!("cannot read level {}: {}", path, e))?;
+        Self::parse(&text, name_or_path).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // resources/levels/にあるステージの名前
+    pub fn builtin_names() -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(LEVELS_DIR)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+                    .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    pub fn parse(text: &str, name: &str) -> Result<Self, String> {
+        let mut title = Path::new(name)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut direction = Direction::Up;
+        let mut rows: Vec<&str> = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if rows.is_empty() {
+                if line.is_empty() {
+                    continue;
+                }
+                if let Some((key, value)) = line.split_once(':') {
+                    let value = value.trim();
+                    match key.trim() {
+                        "title" => title = value.to_string(),
+                        "direction" => {
+                            direction = direction_from_name(value).ok_or_else(|| {
+                                format!("line {}: invalid direction {:?}", i + 1, value)
+                            })?
+                        }
+                        key => return Err(format!("line {}: unknown header {:?}", i + 1, key)),
+                    }
+                    continue;
+                }
+            }
+            if line.is_empty() {
+                break;
+            }
+            rows.push(line);
+        }
+
+        if rows.is_empty() {
+            return Err("no map".to_string());
+        }
+        let width = rows[0].chars().count() as i32;
+        let height = rows.len() as i32;
+
+        let mut start = None;
+        let mut walls = Vec::new();
+        let mut food_zones = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() as i32 != width {
+                return Err(format!("map row {} has a different width", y + 1));
+            }
+            for (x, c) in row.chars().enumerate() {
+                let p = Point::new(x as i32, y as i32);
+                match c {
+                    '.' => {}
+                    '#' => walls.push(p),
+                    '*' => food_zones.push(p),
+                    'S' => {
+                        if start.is_some() {
+                            return Err("more than one start position".to_string());
+                        }
+                        start = Some(p);
+                    }
+                    _ => return Err(format!("map row {}: unknown cell {:?}", y + 1, c)),
+                }
+            }
+        }
+
+        Ok(Level {
+            name: name.to_string(),
+            title,
+            width,
+            height,
+            start: start.ok_or_else(|| "no start position".to_string())?,
+            direction,
+            walls,
+            food_zones,
+        })
+    }
+
+    pub fn is_wall(&self, p: &Point) -> bool {
+        self.walls.contains(p)
+    }
+
+    // 食べ物を出してよいマスか
+    pub fn allows_food(&self, p: &Point) -> bool {
+        if self.food_zones.is_empty() {
+            !self.is_wall(p)
+        } else {
+            self.food_zones.contains(p)
+        }
+    }
+}
+
+fn direction_from_name(name: &str) -> Option<Direction> {
+    match name {
+        "up" => Some(Direction::Up),
+        "down" => Some(Direction::Down),
+        "left" => Some(Direction::Left),
+        "right" => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_header_and_map() {
+        let level = Level::parse(
+            "title: Tiny\ndirection: right\n#####\n#S.*#\n#####\n",
+            "tiny",
+        )
+        .unwrap();
+        assert_eq!(level.name, "tiny");
+        assert_eq!(level.title, "Tiny");
+        assert_eq!((level.width, level.height), (5, 3));
+        assert_eq!(level.start, Point::new(1, 1));
+        assert_eq!(level.direction, Direction::Right);
+        assert_eq!(level.walls.len(), 12);
+        assert_eq!(level.food_zones, vec![Point::new(3, 1)]);
+        assert!(level.is_wall(&Point::new(0, 0)));
+        assert!(!level.allows_food(&Point::new(2, 1)));
+        assert!(level.allows_food(&Point::new(3, 1)));
+    }
+
+    #[test]
+    fn parse_rejects_broken_maps() {
+        assert!(Level::parse("#####\n#...#\n", "x").is_err());
+        assert!(Level::parse("#####\n#S..\n", "x").is_err());
+        assert!(Level::parse("#S.S#\n", "x").is_err());
+        assert!(Level::parse("#S.x#\n", "x").is_err());
+        assert!(Level::parse("direction: north\n#S#\n", "x").is_err());
+    }
+
+    #[test]
+    fn builtin_levels_are_valid() {
+        let names = Level::builtin_names();
+        assert!(names.len() >= 3);
+        for name in names {
+            let level = Level::load(&name).unwrap();
+            assert!(!level.is_wall(&level.start), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,30 @@
+//! Game logic of rust-snake, usable without SDL2.
+//!
+//! ```
+//! use rust_snake::model::{Command, Game};
+//!
+//! let mut game = Game::with_seed(42);
+//! while !game.is_over {
+//!     game.update(Command::None);
+//!     game.take_sounds();
+//! }
+//! println!("score: {}, length: {}", game.players[0].score, game.players[0].bodies.len());
+//! ```
+
+pub mod bindings;
+pub mod bot;
+pub mod cli;
+pub mod font;
+pub mod framebuffer;
+pub mod gamepad;
+pub mod highscore;
+pub mod level;
+pub mod menu;
+pub mod model;
+pub mod render;
+pub mod replay;
+pub mod scoring;
+pub mod sim;
+pub mod sprite;
+pub mod terminal;
+pub mod timestep;
@@ -1,22 +1,36 @@
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use rust_snake::bindings::{Action, KeyBindings};
+use rust_snake::bot::{command_on_move, Autopilot};
+use rust_snake::cli::{self, GameOptions};
+use rust_snake::font::*;
+use rust_snake::gamepad::*;
+use rust_snake::highscore::*;
+use rust_snake::level::*;
+use rust_snake::menu::*;
+use rust_snake::model::*;
+use rust_snake::render::{self, head_pattern, Motion, Overlay, Renderer, Scene, PLAYER_COLORS};
+use rust_snake::replay::*;
+use rust_snake::sprite::{Sprite, SpriteSheet, COLOR_KEY, SPRITES_PATH};
+use rust_snake::timestep::FixedTimestep;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, TextInputUtil};
 use sdl2::mixer;
-use sdl2::pixels::Color;
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::{Point, Rect};
 use sdl2::render::{BlendMode, Canvas, Texture, TextureCreator};
+use sdl2::surface::Surface;
 use sdl2::video::{Window, WindowContext};
 use std::collections::HashMap;
-use std::path::Path;
-use std::time::{Duration, SystemTime};
-mod model;
-use crate::model::*;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
+// 1秒にゲームを進める回数。描く回数とは別で、描くのは垂直同期に合わせるか制限なし
 const FPS: u32 = 30;
-const ATE_COUNT_WIDTH: i32 = 16;
-const ATE_COUNT_HEIGHT: i32 = 16;
-const INFO_MARGIN_TOP: i32 = 2;
-const WHITE_X: i32 = 150;
-const ATE_COUNT_AND_COLOR_WIDTH: i32 = 40;
+// 描くのが遅れたときに1回でまとめて進めるフレーム数の上限
+const MAX_CATCH_UP_TICKS: u32 = 5;
+const REPLAY_FAST_FORWARD: usize = 4;
+// タイトル画面で操作がないままこのフレーム数たつとデモを始める
+const DEMO_IDLE_FRAMES: i32 = FPS as i32 * 10;
 
 struct Image<'a> {
     texture: Texture<'a>,
@@ -27,26 +41,42 @@ struct Image<'a> {
 impl<'a> Image<'a> {
     fn new(texture: Texture<'a>) -> Self {
         let q = texture.query();
-        let image = Image {
+        Image {
             texture,
             w: q.width,
             h: q.height,
-        };
-        image
+        }
     }
 }
 
 struct Resources<'a> {
     images: HashMap<String, Image<'a>>,
     chunks: HashMap<String, sdl2::mixer::Chunk>,
+    text: TextRenderer<'a>,
+    // 読めなかったときは単色で描く
+    sprites: Option<(Image<'a>, SpriteSheet)>,
 }
 
 pub fn main() -> Result<(), String> {
+    let options = parse_args()?;
+    let replay_player = match &options.replay {
+        Some(path) => Some(ReplayPlayer::new(Replay::load(path)?)),
+        None => None,
+    };
+    let config = match &replay_player {
+        Some(player) => options.config_for_replay(&player.replay),
+        None => options.game.config()?,
+    };
+
     let sdl_context = sdl2::init()?;
 
     let video_subsystem = sdl_context.video()?;
     let window = video_subsystem
-        .window("rust-snake", SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)
+        .window(
+            "rust-snake",
+            config.screen_width() as u32,
+            config.screen_height() as u32,
+        )
         .position_centered()
         .opengl()
         .build()
@@ -56,70 +86,673 @@ pub fn main() -> Result<(), String> {
 
     init_mixer();
 
-    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    let mut canvas_builder = window.into_canvas();
+    if options.vsync.unwrap_or(true) {
+        canvas_builder = canvas_builder.present_vsync();
+    }
+    let mut canvas = canvas_builder.build().map_err(|e| e.to_string())?;
     canvas.set_blend_mode(BlendMode::Blend);
 
     let texture_creator = canvas.texture_creator();
-    let mut resources = load_resources(&texture_creator, &mut canvas);
+    let mut resources = load_resources(&texture_creator, &mut canvas, &config);
 
-    let mut event_pump = sdl_context.event_pump()?;
+    // 接続済みのコントローラーも起動直後にControllerDeviceAddedで通知される
+    let controller_subsystem = sdl_context.game_controller()?;
+    // つないだ順。対戦では1台目が1P、2台目が2P
+    // 抜いても番号は詰めずに空けておき、次につないだコントローラーが同じプレイヤーを引き継ぐ
+    let mut controllers: Vec<Option<GameController>> = Vec::new();
 
-    let mut game = Game::new();
+    let mut event_pump = sdl_context.event_pump()?;
 
+    let text_input = video_subsystem.text_input();
+    text_input.stop();
+    let bindings = load_bindings(&options)?;
     println!("Keys:");
-    println!("    Up    : Move player up");
-    println!("    Down  : Move player down");
-    println!("    Left  : Move player left");
-    println!("    Right : Move player right");
-    println!("    Space : Restart when game over");
+    for line in bindings.help() {
+        println!("{}", line);
+    }
+    println!("Controller:");
+    println!("    D-pad / Left stick : Move player, select menu item");
+    println!("    Start              : Pause / resume, select menu item");
+    println!("    A                  : Restart after game over, select menu item");
+    println!("    B                  : Back to title");
+    println!("    In versus the first controller moves player 1 and the second player 2");
+    let mut app = App::new(options, config, replay_player, text_input, bindings);
 
+    let mut timestep = FixedTimestep::per_second(FPS, MAX_CATCH_UP_TICKS);
+    let mut last = Instant::now();
     'running: loop {
-        let started = SystemTime::now();
-
-        let mut command = Command::None;
         for event in event_pump.poll_iter() {
             match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
+                Event::Quit { .. } => app.is_quit = true,
+                Event::Window {
+                    win_event: WindowEvent::FocusLost,
                     ..
-                } => break 'running,
+                } => app.pause_if_playing(),
+                Event::TextInput { text, .. } => app.on_text(&text, &resources.text.font),
                 Event::KeyDown {
-                    keycode: Some(Keycode::Space),
+                    keycode: Some(code),
                     ..
-                } => {
-                    if game.is_over {
-                        game = Game::new();
+                } => app.on_key(code)?,
+                Event::ControllerDeviceAdded { which, .. } => {
+                    match controller_subsystem.open(which) {
+                        Ok(controller) => {
+                            println!("Controller connected: {}", controller.name());
+                            match controllers.iter().position(|x| x.is_none()) {
+                                Some(i) => controllers[i] = Some(controller),
+                                None => controllers.push(Some(controller)),
+                            }
+                        }
+                        Err(e) => println!("cannot open controller {}: {}", which, e),
                     }
                 }
-                Event::KeyDown {
-                    keycode: Some(code),
-                    ..
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    if let Some(i) = controller_slot(&controllers, which) {
+                        if let Some(controller) = controllers[i].take() {
+                            println!("Controller disconnected: {}", controller.name());
+                        }
+                        app.sticks.remove(&i);
+                        app.pause_if_playing();
+                    }
+                }
+                Event::ControllerButtonDown { which, button, .. } => {
+                    if let Some(i) = controller_slot(&controllers, which) {
+                        app.on_button(i, button)?;
+                    }
+                }
+                Event::ControllerAxisMotion {
+                    which, axis, value, ..
                 } => {
-                    command = match code {
-                        Keycode::Left => Command::Left,
-                        Keycode::Right => Command::Right,
-                        Keycode::Up => Command::Up,
-                        Keycode::Down => Command::Down,
-                        _ => Command::None,
-                    };
+                    if let Some(i) = controller_slot(&controllers, which) {
+                        app.on_axis(i, axis, value)?;
+                    }
                 }
                 _ => {}
             }
         }
-        game.update(command);
-        render(&mut canvas, &game, &mut resources)?;
+        if app.is_quit {
+            break 'running;
+        }
+
+        // 描く速さによらず、たった時間の分だけゲームを進める
+        let now = Instant::now();
+        for _ in 0..timestep.advance(now - last) {
+            app.update()?;
+        }
+        last = now;
+        resize_window(&mut canvas, &app.game.config)?;
+        app.render(&mut canvas, &mut resources, timestep.alpha())?;
+
+        // デモの音は鳴らさない
+        let is_demo = matches!(app.state, State::Demo);
+        play_sounds(&mut app.game, &resources, app.is_muted || is_demo);
+    }
+
+    // 途中で終了した場合もそこまでの記録を残す
+    app.abandon()
+}
+
+// 画面の状態
+//
+//   Demo <-> Title -> Playing <-> Paused
+//                        |          |
+//                        v          v
+//            GameOver (-> NameEntry) -> Title / Playing
+enum State {
+    Title,
+    // タイトル画面で放っておくと始まる、自動操縦のゲーム
+    Demo,
+    Playing,
+    Paused,
+    GameOver,
+    // ランクインしたときの名前入力
+    NameEntry(String),
+    // from_titleならタイトルに、そうでなければゲームオーバー画面に戻る
+    HighScores { from_title: bool },
+}
+
+struct App {
+    options: Options,
+    state: State,
+    menu: Menu,
+    // タイトル画面では選んでいる盤面のプレビュー
+    game: Game,
+    // プレイヤーごとに次のフレームまでに押されたコマンド。押された順
+    commands: Vec<Vec<Command>>,
+    recording: Replay,
+    replay_player: Option<ReplayPlayer>,
+    fast_forward: bool,
+    step: bool,
+    high_scores: HighScores,
+    scores_path: Option<PathBuf>,
+    player_name: String,
+    text_input: TextInputUtil,
+    bindings: KeyBindings,
+    // コントローラーをつないだ順の番号ごとのスティック
+    sticks: HashMap<usize, Stick>,
+    // タイトル画面で操作がないまま過ぎたフレーム数
+    idle_frames: i32,
+    is_muted: bool,
+    is_quit: bool,
+}
+
+impl App {
+    fn new(
+        options: Options,
+        config: GameConfig,
+        replay_player: Option<ReplayPlayer>,
+        text_input: TextInputUtil,
+        bindings: KeyBindings,
+    ) -> Self {
+        let scores_path = default_path();
+        let high_scores = match &scores_path {
+            Some(path) => HighScores::load(path).unwrap_or_else(|e| {
+                println!("cannot load high scores: {}", e);
+                HighScores::default()
+            }),
+            None => HighScores::default(),
+        };
+        let game = Game::with_config(config.clone(), 0);
+        let mut app = App {
+            options,
+            state: State::Title,
+            menu: Menu::new(&config, Level::builtin_names()),
+            recording: Replay::new(&game),
+            game,
+            commands: Vec::new(),
+            replay_player,
+            fast_forward: false,
+            step: false,
+            high_scores,
+            scores_path,
+            player_name: String::new(),
+            text_input,
+            bindings,
+            sticks: HashMap::new(),
+            idle_frames: 0,
+            is_muted: false,
+            is_quit: false,
+        };
+        // リプレイはタイトル画面を出さずにすぐ始める
+        if app.replay_player.is_some() {
+            app.start();
+        }
+        app
+    }
+
+    fn seed(&self) -> Option<u64> {
+        match &self.replay_player {
+            Some(player) => Some(player.replay.seed),
+            None => self.options.game.seed,
+        }
+    }
+
+    fn start(&mut self) {
+        self.game = new_game(&self.game.config, self.seed());
+        self.commands = vec![Vec::new(); self.game.players.len()];
+        self.recording = Replay::new(&self.game);
+        if let Some(player) = &mut self.replay_player {
+            player.rewind();
+        }
+        self.state = State::Playing;
+    }
+
+    // 遊んでいる途中ならそこまでの記録を保存する
+    fn abandon(&mut self) -> Result<(), String> {
+        if matches!(self.state, State::Playing | State::Paused) && self.replay_player.is_none() {
+            save_recording(&self.options, &self.recording)?;
+        }
+        Ok(())
+    }
+
+    fn back_to_title(&mut self) -> Result<(), String> {
+        self.abandon()?;
+        if self.replay_player.is_some() {
+            self.is_quit = true;
+            return Ok(());
+        }
+        self.state = State::Title;
+        self.refresh_preview();
+        Ok(())
+    }
+
+    fn refresh_preview(&mut self) {
+        match self.menu.config() {
+            Ok(config) => self.game = Game::with_config(config, 0),
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    // デモは記録もハイスコアも残さない。対戦を選んでいても1人で遊ぶ
+    fn start_demo(&mut self) {
+        match self.menu.config() {
+            Ok(config) => {
+                self.game = Game::with_config(
+                    GameConfig {
+                        players: 1,
+                        ..config
+                    },
+                    time_seed(),
+                );
+                self.state = State::Demo;
+            }
+            Err(e) => println!("{}", e),
+        }
+        self.idle_frames = 0;
+    }
+
+    // 何か操作されたらデモをやめてタイトル画面に戻る。デモをやめたらtrue
+    fn wake(&mut self) -> bool {
+        self.idle_frames = 0;
+        if let State::Demo = self.state {
+            self.state = State::Title;
+            self.refresh_preview();
+            return true;
+        }
+        false
+    }
+
+    fn game_over(&mut self) -> Result<(), String> {
+        self.state = State::GameOver;
+        if self.replay_player.is_some() {
+            return Ok(());
+        }
+        save_recording(&self.options, &self.recording)?;
+        // 対戦はハイスコアに残さない
+        if !self.game.config.is_versus()
+            && self
+                .high_scores
+                .qualifies(&mode_key(&self.game.config), self.game.players[0].score)
+        {
+            self.state = State::NameEntry(self.player_name.clone());
+            self.text_input.start();
+        }
+        Ok(())
+    }
+
+    fn save_high_score(&mut self, name: &str) {
+        self.player_name = sanitize_name(name);
+        self.high_scores.insert(
+            &mode_key(&self.game.config),
+            HighScore::new(&self.player_name, &self.game, unix_timestamp()),
+        );
+        if let Some(path) = &self.scores_path {
+            if let Err(e) = self.high_scores.save(path) {
+                println!("cannot save high scores: {}", e);
+            }
+        }
+    }
+
+    // ウィンドウが隠れたりコントローラーが抜けたりしたら止める
+    fn pause_if_playing(&mut self) {
+        if let State::Playing = self.state {
+            self.state = State::Paused;
+        }
+    }
+
+    // 名前にはフォントにある文字だけ使える
+    fn on_text(&mut self, text: &str, font: &Font) {
+        if let State::NameEntry(name) = &mut self.state {
+            for c in text.chars() {
+                if font.has(c) && name.chars().count() < NAME_MAX_LEN {
+                    name.push(c);
+                }
+            }
+        }
+    }
+
+    fn on_key(&mut self, code: Keycode) -> Result<(), String> {
+        if self.wake() {
+            return Ok(());
+        }
+        // 名前の入力中は文字を打てるようにキーの割り当てを使わない
+        if let State::NameEntry(name) = &mut self.state {
+            match code {
+                Keycode::Backspace => {
+                    name.pop();
+                }
+                Keycode::Return | Keycode::KpEnter => self.finish_name_entry(true),
+                Keycode::Escape => self.finish_name_entry(false),
+                _ => {}
+            }
+            return Ok(());
+        }
+        self.on_actions(&self.bindings.actions(&code.name()))
+    }
+
+    // playerはコントローラーをつないだ順の番号
+    fn on_button(&mut self, player: usize, button: Button) -> Result<(), String> {
+        if self.wake() {
+            return Ok(());
+        }
+        if let State::NameEntry(_) = self.state {
+            match button {
+                Button::A | Button::Start => self.finish_name_entry(true),
+                Button::B => self.finish_name_entry(false),
+                _ => {}
+            }
+            return Ok(());
+        }
+        let actions = match button {
+            Button::DPadUp => direction_actions(player, Direction::Up),
+            Button::DPadDown => direction_actions(player, Direction::Down),
+            Button::DPadLeft => direction_actions(player, Direction::Left),
+            Button::DPadRight => direction_actions(player, Direction::Right),
+            Button::Start => vec![Action::Pause, Action::Select],
+            Button::A => vec![Action::Restart, Action::Select],
+            Button::B => vec![Action::Quit],
+            _ => vec![],
+        };
+        self.on_actions(&actions)
+    }
+
+    fn on_axis(&mut self, player: usize, axis: Axis, value: i16) -> Result<(), String> {
+        let stick = self.sticks.entry(player).or_default();
+        let direction = match axis {
+            Axis::LeftX => stick.set_x(value),
+            Axis::LeftY => stick.set_y(value),
+            _ => None,
+        };
+        match direction {
+            Some(_) if self.wake() => Ok(()),
+            Some(direction) => self.on_actions(&direction_actions(player, direction)),
+            None => Ok(()),
+        }
+    }
+
+    fn finish_name_entry(&mut self, save: bool) {
+        if let State::NameEntry(name) = &self.state {
+            if save {
+                let name = name.clone();
+                self.save_high_score(&name);
+            }
+            self.text_input.stop();
+            self.state = if save {
+                State::HighScores { from_title: false }
+            } else {
+                State::GameOver
+            };
+        }
+    }
+
+    // 1つの入力に複数の操作があるときは、今の画面で使える最初のものだけ行う
+    fn on_actions(&mut self, actions: &[Action]) -> Result<(), String> {
+        for action in actions {
+            if self.on_action(*action)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // 操作を行ったらtrue
+    fn on_action(&mut self, action: Action) -> Result<bool, String> {
+        let is_replay = self.replay_player.is_some();
+        if let (State::Playing, Some((player, command))) =
+            (&self.state, action.steering(self.game.config.is_versus()))
+        {
+            self.commands[player].push(command);
+            return Ok(true);
+        }
+        match (&self.state, action) {
+            (_, Action::Mute) => self.is_muted = !self.is_muted,
+            (_, Action::FastForward) if is_replay => self.fast_forward = !self.fast_forward,
+            (State::Title, Action::Up) => self.menu.move_cursor(-1),
+            (State::Title, Action::Down) => self.menu.move_cursor(1),
+            (State::Title, Action::Left) => {
+                if self.menu.change(-1) {
+                    self.refresh_preview();
+                }
+            }
+            (State::Title, Action::Right) => {
+                if self.menu.change(1) {
+                    self.refresh_preview();
+                }
+            }
+            (State::Title, Action::Select) => match self.menu.selected() {
+                MenuItem::Start => self.start(),
+                MenuItem::HighScores => self.state = State::HighScores { from_title: true },
+                MenuItem::Quit => self.is_quit = true,
+                _ => {
+                    self.menu.change(1);
+                    self.refresh_preview();
+                }
+            },
+            (State::Title, Action::HighScores) => {
+                self.state = State::HighScores { from_title: true }
+            }
+            (State::Title, Action::Quit) => self.is_quit = true,
+            (State::Playing, Action::Pause) => self.state = State::Paused,
+            (State::Paused, Action::Pause) => self.state = State::Playing,
+            (State::Paused, Action::Step) if is_replay => self.step = true,
+            (State::Playing | State::Paused, Action::Quit) => self.back_to_title()?,
+            (State::GameOver, Action::Restart) => self.start(),
+            (State::GameOver, Action::Select | Action::Quit) => self.back_to_title()?,
+            (State::GameOver, Action::HighScores)
+                if !is_replay && !self.game.config.is_versus() =>
+            {
+                self.state = State::HighScores { from_title: false }
+            }
+            (
+                State::HighScores { from_title },
+                Action::Select | Action::Quit | Action::HighScores,
+            ) => {
+                self.state = if *from_title {
+                    State::Title
+                } else {
+                    State::GameOver
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // 1フレーム進める。PlayingとDemo以外ではゲームは止まっている
+    fn update(&mut self) -> Result<(), String> {
+        match self.state {
+            State::Title => {
+                self.idle_frames += 1;
+                if self.idle_frames >= DEMO_IDLE_FRAMES {
+                    self.start_demo();
+                }
+                return Ok(());
+            }
+            State::Demo => {
+                let command = command_on_move(&mut Autopilot, &self.game, 0);
+                self.game.update(command);
+                if self.game.is_over {
+                    self.wake();
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+        let empty = vec![Vec::new(); self.game.players.len()];
+        let rounds = command_rounds(&std::mem::replace(&mut self.commands, empty));
+        let step = std::mem::replace(&mut self.step, false);
+        let updates = match self.state {
+            State::Playing if self.replay_player.is_some() && self.fast_forward => {
+                REPLAY_FAST_FORWARD
+            }
+            State::Playing => 1,
+            State::Paused if self.replay_player.is_some() && step => 1,
+            _ => 0,
+        };
+        for _ in 0..updates {
+            if self.game.is_over {
+                break;
+            }
+            match &mut self.replay_player {
+                Some(player) => {
+                    let rounds = player.rounds_at(self.game.frame);
+                    self.game.update_rounds(&rounds);
+                }
+                None => {
+                    for commands in &rounds {
+                        self.recording.record(self.game.frame, commands);
+                    }
+                    self.game.update_rounds(&rounds);
+                }
+            }
+            if self.game.is_over {
+                self.game_over()?;
+            }
+        }
+        Ok(())
+    }
+
+    // 画面に出す操作説明。最初に割り当てたキーを使う
+    fn key_hint(&self, action: Action) -> String {
+        match self.bindings.keys(action).first() {
+            Some(key) => key.to_uppercase(),
+            None => "-".to_string(),
+        }
+    }
+
+    // alphaは前のフレームから次のフレームまでの進み具合。ゲームが止まっているときは使わない
+    fn render(
+        &self,
+        canvas: &mut Canvas<Window>,
+        resources: &mut Resources,
+        alpha: f32,
+    ) -> Result<(), String> {
+        let overlay = match &self.state {
+            State::Title => Overlay::Title(&self.menu),
+            State::Demo => Overlay::Demo,
+            State::Playing => Overlay::None,
+            State::Paused => Overlay::Paused(format!(
+                "{}: RESUME  {}: TITLE",
+                self.key_hint(Action::Pause),
+                self.key_hint(Action::Quit)
+            )),
+            State::GameOver => Overlay::GameOver(format!(
+                "{}: RETRY  {}: TITLE  {}: SCORES",
+                self.key_hint(Action::Restart),
+                self.key_hint(Action::Select),
+                self.key_hint(Action::HighScores)
+            )),
+            State::NameEntry(name) => Overlay::NameEntry(name),
+            State::HighScores { .. } => {
+                Overlay::HighScores(self.high_scores.top(&mode_key(&self.game.config)))
+            }
+        };
+        let scene = Scene {
+            game: &self.game,
+            overlay,
+            motion: self.options.motion.unwrap_or_default(),
+            alpha: match self.state {
+                State::Playing | State::Demo => alpha,
+                _ => 1.0,
+            },
+        };
+        render::render_scene(&mut SdlRenderer { canvas, resources }, &scene)?;
+        canvas.present();
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Options {
+    game: GameOptions,
+    record: Option<String>,
+    replay: Option<String>,
+    motion: Option<Motion>,
+    vsync: Option<bool>,
+}
+
+impl Options {
+    // リプレイでは盤面の設定は記録されたものを使い、表示の大きさだけ指定できる
+    fn config_for_replay(&self, replay: &Replay) -> GameConfig {
+        GameConfig {
+            cell_size: self.game.cell_size.unwrap_or(replay.config.cell_size),
+            ..replay.config.clone()
+        }
+    }
+}
 
-        play_sounds(&mut game, &resources);
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options::default();
+    for (name, value) in cli::split_args(std::env::args().skip(1))? {
+        match name.as_str() {
+            "--record" => options.record = Some(value),
+            "--replay" => options.replay = Some(value),
+            "--vsync" => {
+                options.vsync = Some(match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(format!("invalid vsync {:?}", value)),
+                });
+            }
+            "--motion" => {
+                options.motion = Some(
+                    Motion::from_name(&value)
+                        .ok_or_else(|| format!("invalid motion {:?}", value))?,
+                );
+            }
+            _ if options.game.parse(&name, &value)? => {}
+            _ => return Err(cli::unknown_argument(&name)),
+        }
+    }
+    Ok(options)
+}
 
-        let finished = SystemTime::now();
-        let elapsed = finished.duration_since(started).unwrap();
-        let frame_duration = Duration::new(0, 1_000_000_000u32 / FPS);
-        if elapsed < frame_duration {
-            ::std::thread::sleep(frame_duration - elapsed)
+// SDLにない名前のキーは割り当てても押せないので知らせる
+fn load_bindings(options: &Options) -> Result<KeyBindings, String> {
+    let bindings = options.game.bindings()?;
+    for key in bindings.all_keys() {
+        if Keycode::from_name(key).is_none() {
+            println!("unknown key name in key bindings: {:?}", key);
         }
     }
+    Ok(bindings)
+}
+
+// コントローラーの方向の操作。対戦ではつないだ順に1P・2Pを動かす
+// instance_idのコントローラーがつながっている番号
+fn controller_slot(controllers: &[Option<GameController>], which: u32) -> Option<usize> {
+    controllers
+        .iter()
+        .position(|x| x.as_ref().is_some_and(|x| x.instance_id() == which))
+}
+
+fn direction_actions(player: usize, direction: Direction) -> Vec<Action> {
+    let (any, p1, p2) = match direction {
+        Direction::Up => (Action::Up, Action::P1Up, Action::P2Up),
+        Direction::Down => (Action::Down, Action::P1Down, Action::P2Down),
+        Direction::Left => (Action::Left, Action::P1Left, Action::P2Left),
+        Direction::Right => (Action::Right, Action::P1Right, Action::P2Right),
+    };
+    match player {
+        0 => vec![any, p1],
+        1 => vec![any, p2],
+        _ => vec![any],
+    }
+}
+
+fn new_game(config: &GameConfig, seed: Option<u64>) -> Game {
+    let game = Game::with_config(config.clone(), seed.unwrap_or_else(time_seed));
+    println!("Seed: {}", game.seed);
+    game
+}
+
+fn save_recording(options: &Options, recording: &Replay) -> Result<(), String> {
+    if let Some(path) = &options.record {
+        recording.save(path)?;
+        println!("Saved replay: {}", path);
+    }
+    Ok(())
+}
 
+// メニューで盤面の大きさが変わったらウィンドウも合わせる
+fn resize_window(canvas: &mut Canvas<Window>, config: &GameConfig) -> Result<(), String> {
+    let size = (config.screen_width() as u32, config.screen_height() as u32);
+    if canvas.window().size() != size {
+        canvas
+            .window_mut()
+            .set_size(size.0, size.1)
+            .map_err(|e| e.to_string())?;
+    }
     Ok(())
 }
 
@@ -138,45 +771,21 @@ fn init_mixer() {
 fn load_resources<'a>(
     texture_creator: &'a TextureCreator<WindowContext>,
     canvas: &mut Canvas<Window>,
+    config: &GameConfig,
 ) -> Resources<'a> {
     let mut resources = Resources {
         images: HashMap::new(),
         chunks: HashMap::new(),
+        text: TextRenderer::new(texture_creator, Font::builtin()).expect("cannot create font"),
+        sprites: None,
     };
 
-    // create head texture
-    let mut head_texture = texture_creator
-        .create_texture(
-            None,
-            sdl2::render::TextureAccess::Target,
-            CELL_SIZE as u32,
-            CELL_SIZE as u32,
-        )
-        .unwrap();
-    canvas
-        .with_texture_canvas(&mut head_texture, |texture_canvas| {
-            texture_canvas.set_draw_color(Color::RGBA(61, 122, 61, 255));
-            texture_canvas
-                .fill_rect(Rect::new(0, 0, CELL_SIZE as u32, CELL_SIZE as u32))
-                .unwrap();
-
-            // 黒目
-            texture_canvas.set_draw_color(Color::RGBA(255, 255, 255, 255));
-            texture_canvas.fill_rect(Rect::new(6, 0, 3, 3)).unwrap();
-            texture_canvas
-                .fill_rect(Rect::new(CELL_SIZE - 6 - 1, 0, 3, 3))
-                .unwrap();
-
-            // 白目
-            texture_canvas.set_draw_color(Color::RGBA(0, 0, 0, 255));
-            texture_canvas.fill_rect(Rect::new(6 + 2, 0, 2, 2)).unwrap();
-            texture_canvas
-                .fill_rect(Rect::new(CELL_SIZE - 6 - 1, 0, 2, 2))
-                .unwrap();
-        })
-        .unwrap();
-    let head_image = Image::new(head_texture);
-    resources.images.insert("head".to_string(), head_image);
+    for (i, color) in PLAYER_COLORS.into_iter().enumerate() {
+        let texture = create_head_texture(texture_creator, canvas, config.cell_size, color);
+        resources
+            .images
+            .insert(format!("head{}", i), Image::new(texture));
+    }
 
     let image_paths = ["numbers.bmp"];
     for path in image_paths {
@@ -184,12 +793,17 @@ fn load_resources<'a>(
         let temp_surface = sdl2::surface::Surface::load_bmp(Path::new(&full_path)).unwrap();
         let texture = texture_creator
             .create_texture_from_surface(&temp_surface)
-            .expect(&format!("cannot load image: {}", path));
+            .unwrap_or_else(|_| panic!("cannot load image: {}", path));
 
         let image = Image::new(texture);
         resources.images.insert(path.to_string(), image);
     }
 
+    match load_sprites(texture_creator) {
+        Ok(sprites) => resources.sprites = Some(sprites),
+        Err(e) => println!("cannot load {}: {}, using flat colors", SPRITES_PATH, e),
+    }
+
     let sound_paths = [
         "crash.wav",
         "eat.wav",
@@ -198,185 +812,230 @@ fn load_resources<'a>(
         "d4.wav",
         "e4.wav",
         "g4.wav",
+        "pi.wav",
     ];
     for path in sound_paths {
         let full_path = "resources/sound/".to_string() + path;
-        let chunk =
-            mixer::Chunk::from_file(full_path).expect(&format!("cannot load sound: {}", path));
+        let chunk = mixer::Chunk::from_file(full_path)
+            .unwrap_or_else(|_| panic!("cannot load sound: {}", path));
         resources.chunks.insert(path.to_string(), chunk);
     }
 
     resources
 }
 
-fn render(
+fn load_sprites(
+    texture_creator: &TextureCreator<WindowContext>,
+) -> Result<(Image<'_>, SpriteSheet), String> {
+    let mut surface = Surface::load_bmp(Path::new(SPRITES_PATH))?;
+    let sheet = SpriteSheet::new(surface.width() as i32, surface.height() as i32)?;
+    surface.set_color_key(true, sdl_color(COLOR_KEY))?;
+    let texture = texture_creator
+        .create_texture_from_surface(&surface)
+        .map_err(|e| e.to_string())?;
+    Ok((Image::new(texture), sheet))
+}
+
+// プレイヤーの色の上を向いた頭。描くときに向きに合わせて回す
+fn create_head_texture<'a>(
+    texture_creator: &'a TextureCreator<WindowContext>,
     canvas: &mut Canvas<Window>,
-    game: &Game,
-    resources: &mut Resources,
-) -> Result<(), String> {
-    canvas.set_draw_color(Color::RGB(154, 230, 192));
-    canvas.clear();
-
-    // render foods
-    for food in &game.foods {
-        if food.is_exist {
-            let color = get_draw_color(&food.color);
-            canvas.set_draw_color(color);
-            canvas.fill_rect(Rect::new(
-                food.p.x * CELL_SIZE,
-                food.p.y * CELL_SIZE + INFO_HEIGHT,
-                CELL_SIZE as u32,
-                CELL_SIZE as u32,
-            ))?;
-        }
-    }
-
-    // render poos
-    for poo in &game.poos {
-        if poo.is_exist {
-            let color = Color::RGB(92, 48, 28);
-            canvas.set_draw_color(color);
-            canvas.fill_rect(Rect::new(
-                poo.p.x * CELL_SIZE,
-                poo.p.y * CELL_SIZE + INFO_HEIGHT,
-                CELL_SIZE as u32,
-                CELL_SIZE as u32,
-            ))?;
-        }
-    }
-
-    // render bodies
-    canvas.set_draw_color(Color::RGBA(61, 122, 61, 255));
-    for body in &game.player.bodies {
-        canvas.fill_rect(Rect::new(
-            body.x * CELL_SIZE,
-            body.y * CELL_SIZE + INFO_HEIGHT,
-            CELL_SIZE as u32,
-            CELL_SIZE as u32,
-        ))?;
-    }
-
-    // render head
-    let head = resources.images.get_mut("head").unwrap();
-    canvas.copy_ex(
-        &head.texture,
-        None,
-        Rect::new(
-            game.player.p.x * CELL_SIZE,
-            game.player.p.y * CELL_SIZE + INFO_HEIGHT,
-            CELL_SIZE as u32,
-            CELL_SIZE as u32,
-        ),
-        game.player.get_angle() as f64, /* SDLのangleは時計回りが正 */
-        Point::new(CELL_SIZE / 2, CELL_SIZE / 2),
-        false,
-        false,
-    )?;
-
-    if game.is_over {
-        canvas.set_draw_color(Color::RGBA(255, 0, 0, 128));
-        canvas.fill_rect(Rect::new(0, 0, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32))?;
-    }
-
-    // render info
-    canvas.set_draw_color(Color::RGB(64, 64, 64));
-    canvas.fill_rect(Rect::new(0, 0, SCREEN_WIDTH as u32, INFO_HEIGHT as u32))?;
-
-    // energy
-    let max_energy_width = 120;
-    canvas.set_draw_color(Color::RGB(32, 32, 32));
-    canvas.fill_rect(Rect::new(0, 0, max_energy_width, INFO_HEIGHT as u32))?;
-    canvas.set_draw_color(Color::RGB(128, 255, 128));
-    canvas.fill_rect(Rect::new(
-        0,
-        0,
-        (max_energy_width as f32 * game.player.energy as f32 / 100.0).floor() as u32,
-        INFO_HEIGHT as u32,
-    ))?;
-
-    let mut x = WHITE_X - ATE_COUNT_AND_COLOR_WIDTH;
-
-    // ate counts
-    for color in FoodColor::all() {
-        x += ATE_COUNT_AND_COLOR_WIDTH;
-        let draw_color = get_draw_color(&color);
-        canvas.set_draw_color(draw_color);
-        canvas.fill_rect(Rect::new(
-            x,
-            INFO_MARGIN_TOP,
-            ATE_COUNT_WIDTH as u32,
-            ATE_COUNT_HEIGHT as u32,
-        ))?;
-        x += ATE_COUNT_WIDTH + 4;
-        render_number(
-            canvas,
-            resources,
-            x,
-            INFO_MARGIN_TOP,
-            format!("{0: >3}", game.ate_counts[&color.clone()]),
-        );
-    }
+    cell_size: i32,
+    color: render::Color,
+) -> Texture<'a> {
+    let mut head_texture = texture_creator
+        .create_texture(
+            None,
+            sdl2::render::TextureAccess::Target,
+            cell_size as u32,
+            cell_size as u32,
+        )
+        .unwrap();
+    canvas
+        .with_texture_canvas(&mut head_texture, |texture_canvas| {
+            for (rect, color) in head_pattern(cell_size, color) {
+                texture_canvas.set_draw_color(sdl_color(color));
+                texture_canvas.fill_rect(sdl_rect(rect)).unwrap();
+            }
+        })
+        .unwrap();
+    head_texture
+}
 
-    render_number(
-        canvas,
-        resources,
-        SCREEN_WIDTH as i32 - 8 * 8,
-        INFO_MARGIN_TOP,
-        format!("{0: >8}", game.score),
-    );
+// フォントのアトラスから1文字ずつ切り出して描く
+struct TextRenderer<'a> {
+    font: Font,
+    texture: Texture<'a>,
+    entries: HashMap<char, AtlasEntry>,
+}
 
-    canvas.present();
+impl<'a> TextRenderer<'a> {
+    fn new(texture_creator: &'a TextureCreator<WindowContext>, font: Font) -> Result<Self, String> {
+        let atlas = font.atlas();
+        let mut surface = Surface::new(
+            atlas.width as u32,
+            atlas.height as u32,
+            PixelFormatEnum::RGBA32,
+        )?;
+        let pitch = surface.pitch() as usize;
+        surface.with_lock_mut(|pixels| {
+            for (i, is_set) in atlas.pixels.iter().enumerate() {
+                if *is_set {
+                    let x = i % atlas.width as usize;
+                    let y = i / atlas.width as usize;
+                    let offset = y * pitch + x * 4;
+                    pixels[offset..offset + 4].copy_from_slice(&[255, 255, 255, 255]);
+                }
+            }
+        });
+        let mut texture = texture_creator
+            .create_texture_from_surface(&surface)
+            .map_err(|e| e.to_string())?;
+        texture.set_blend_mode(BlendMode::Blend);
+        Ok(TextRenderer {
+            font,
+            texture,
+            entries: atlas.entries,
+        })
+    }
+}
 
-    Ok(())
+// SDL2のウィンドウに描く
+struct SdlRenderer<'a, 'b> {
+    canvas: &'a mut Canvas<Window>,
+    resources: &'a mut Resources<'b>,
 }
 
-fn get_draw_color(food_color: &FoodColor) -> Color {
-    match food_color {
-        FoodColor::White => Color::RGB(209, 220, 230),
-        FoodColor::Red => Color::RGB(255, 128, 128),
-        FoodColor::Yellow => Color::RGB(255, 255, 128),
-        FoodColor::Blue => Color::RGB(128, 128, 255),
+impl Renderer for SdlRenderer<'_, '_> {
+    fn font(&self) -> &Font {
+        &self.resources.text.font
     }
-}
 
-fn render_number(
-    canvas: &mut Canvas<Window>,
-    resources: &Resources,
-    x: i32,
-    y: i32,
-    numstr: String,
-) {
-    let mut x = x;
-    let image = resources.images.get("numbers.bmp").unwrap();
-    let digit_width_in_px = 8;
-    for c in numstr.chars() {
-        if 0x30 <= c as i32 && c as i32 <= 0x39 {
-            canvas
-                .copy(
-                    &image.texture,
-                    Rect::new(
-                        digit_width_in_px * (c as i32 - 0x30),
-                        0,
-                        digit_width_in_px as u32,
-                        image.h,
-                    ),
-                    Rect::new(x, y, digit_width_in_px as u32, image.h),
-                )
-                .unwrap();
-        }
-        x += digit_width_in_px;
+    fn digit_size(&self) -> (i32, i32) {
+        let image = &self.resources.images["numbers.bmp"];
+        (image.w as i32 / 10, image.h as i32)
+    }
+
+    fn clear(&mut self, color: render::Color) -> Result<(), String> {
+        self.canvas.set_draw_color(sdl_color(color));
+        self.canvas.clear();
+        Ok(())
     }
+
+    fn fill_rect(&mut self, rect: render::Rect, color: render::Color) -> Result<(), String> {
+        // SDLのRectは幅0を1にしてしまう
+        if rect.w <= 0 || rect.h <= 0 {
+            return Ok(());
+        }
+        self.canvas.set_draw_color(sdl_color(color));
+        self.canvas.fill_rect(sdl_rect(rect))
+    }
+
+    fn draw_rect(&mut self, rect: render::Rect, color: render::Color) -> Result<(), String> {
+        if rect.w <= 0 || rect.h <= 0 {
+            return Ok(());
+        }
+        self.canvas.set_draw_color(sdl_color(color));
+        self.canvas.draw_rect(sdl_rect(rect))
+    }
+
+    fn draw_digit(&mut self, digit: u32, x: i32, y: i32) -> Result<(), String> {
+        let (w, h) = self.digit_size();
+        let image = &self.resources.images["numbers.bmp"];
+        self.canvas.copy(
+            &image.texture,
+            Rect::new(w * digit as i32, 0, w as u32, h as u32),
+            Rect::new(x, y, w as u32, h as u32),
+        )
+    }
+
+    fn draw_glyph(
+        &mut self,
+        c: char,
+        x: i32,
+        y: i32,
+        scale: i32,
+        color: render::Color,
+    ) -> Result<(), String> {
+        let text = &mut self.resources.text;
+        let entry = text.entries[&c];
+        let height = text.font.height;
+        text.texture.set_color_mod(color.r, color.g, color.b);
+        text.texture.set_alpha_mod(color.a);
+        self.canvas.copy(
+            &text.texture,
+            Rect::new(entry.x, entry.y, entry.width as u32, height as u32),
+            Rect::new(x, y, (entry.width * scale) as u32, (height * scale) as u32),
+        )
+    }
+
+    fn draw_head(
+        &mut self,
+        index: usize,
+        direction: Direction,
+        rect: render::Rect,
+    ) -> Result<(), String> {
+        let head = &self.resources.images[&format!("head{}", index)];
+        self.canvas.copy_ex(
+            &head.texture,
+            None,
+            sdl_rect(rect),
+            direction.angle() as f64, /* SDLのangleは時計回りが正 */
+            Point::new(rect.w / 2, rect.h / 2),
+            false,
+            false,
+        )
+    }
+
+    fn set_clip(&mut self, rect: Option<render::Rect>) -> Result<(), String> {
+        self.canvas.set_clip_rect(rect.map(sdl_rect));
+        Ok(())
+    }
+
+    fn has_sprites(&self) -> bool {
+        self.resources.sprites.is_some()
+    }
+
+    fn draw_sprite(
+        &mut self,
+        sprite: &Sprite,
+        direction: Direction,
+        rect: render::Rect,
+        tint: render::Color,
+    ) -> Result<(), String> {
+        let Some((image, sheet)) = &mut self.resources.sprites else {
+            return Err("sprites are not loaded".to_string());
+        };
+        image.texture.set_color_mod(tint.r, tint.g, tint.b);
+        image.texture.set_alpha_mod(tint.a);
+        self.canvas.copy_ex(
+            &image.texture,
+            sdl_rect(sheet.tile(sprite)),
+            sdl_rect(rect),
+            direction.angle() as f64,
+            Point::new(rect.w / 2, rect.h / 2),
+            false,
+            false,
+        )
+    }
+}
+
+fn sdl_color(color: render::Color) -> Color {
+    Color::RGBA(color.r, color.g, color.b, color.a)
 }
 
-fn play_sounds(game: &mut Game, resources: &Resources) {
-    for sound_key in &game.requested_sounds {
-        let chunk = resources
-            .chunks
-            .get(&sound_key.to_string())
-            .expect("cannot get sound");
+fn sdl_rect(rect: render::Rect) -> Rect {
+    Rect::new(rect.x, rect.y, rect.w as u32, rect.h as u32)
+}
+
+fn play_sounds(game: &mut Game, resources: &Resources, is_muted: bool) {
+    for sound_key in game.take_sounds() {
+        if is_muted {
+            continue;
+        }
+        let chunk = resources.chunks.get(sound_key).expect("cannot get sound");
         sdl2::mixer::Channel::all()
-            .play(&chunk, 0)
+            .play(chunk, 0)
             .expect("cannot play sound");
     }
-    game.requested_sounds = Vec::new();
 }
@@ -0,0 +1,216 @@
+use crate::bot::BotLevel;
+use crate::level::Level;
+use crate::model::{Boundary, Difficulty, GameConfig, BOTS_MAX, PLAYERS_MAX};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuItem {
+    Start,
+    Players,
+    Bots,
+    BotLevel,
+    Boundary,
+    Difficulty,
+    Level,
+    HighScores,
+    Quit,
+}
+
+impl MenuItem {
+    pub fn all() -> Vec<Self> {
+        vec![
+            MenuItem::Start,
+            MenuItem::Players,
+            MenuItem::Bots,
+            MenuItem::BotLevel,
+            MenuItem::Boundary,
+            MenuItem::Difficulty,
+            MenuItem::Level,
+            MenuItem::HighScores,
+            MenuItem::Quit,
+        ]
+    }
+}
+
+// タイトル画面のメニュー。選んだ設定からGameConfigを作る
+#[derive(Clone, Debug)]
+pub struct Menu {
+    pub cursor: usize,
+    pub players: usize,
+    pub bots: usize,
+    pub bot_level: BotLevel,
+    pub boundary: Boundary,
+    pub difficulty: Difficulty,
+    // 選べるステージの名前
+    pub levels: Vec<String>,
+    // 0はステージなし、1以降はlevels[level - 1]
+    pub level: usize,
+    // ステージなしのときの盤面
+    pub width: i32,
+    pub height: i32,
+    pub cell_size: i32,
+}
+
+impl Menu {
+    // configの設定を選んだ状態で始める
+    pub fn new(config: &GameConfig, levels: Vec<String>) -> Self {
+        let mut menu = Menu {
+            cursor: 0,
+            players: config.players,
+            bots: config.bots,
+            bot_level: config.bot_level,
+            boundary: config.boundary,
+            difficulty: config.difficulty,
+            levels,
+            level: 0,
+            width: config.width,
+            height: config.height,
+            cell_size: config.cell_size,
+        };
+        if let Some(level) = &config.level {
+            let default = GameConfig::default();
+            menu.width = default.width;
+            menu.height = default.height;
+            // パスで指定されたステージも選べるようにする
+            if !menu.levels.contains(&level.name) {
+                menu.levels.push(level.name.clone());
+            }
+            menu.level = menu.levels.iter().position(|x| *x == level.name).unwrap() + 1;
+        }
+        menu
+    }
+
+    pub fn selected(&self) -> MenuItem {
+        MenuItem::all()[self.cursor]
+    }
+
+    pub fn move_cursor(&mut self, delta: i32) {
+        self.cursor = cycle(self.cursor, MenuItem::all().len(), delta);
+    }
+
+    // 選んでいる項目の値を変える。設定が変わったらtrue
+    pub fn change(&mut self, delta: i32) -> bool {
+        match self.selected() {
+            MenuItem::Players => self.players = cycle(self.players - 1, PLAYERS_MAX, delta) + 1,
+            MenuItem::Bots => self.bots = cycle(self.bots, BOTS_MAX + 1, delta),
+            MenuItem::BotLevel => {
+                let all = BotLevel::all();
+                let i = all.iter().position(|x| *x == self.bot_level).unwrap();
+                self.bot_level = all[cycle(i, all.len(), delta)];
+            }
+            MenuItem::Boundary => {
+                let all = Boundary::all();
+                let i = all.iter().position(|x| *x == self.boundary).unwrap();
+                self.boundary = all[cycle(i, all.len(), delta)];
+            }
+            MenuItem::Difficulty => {
+                let all = Difficulty::all();
+                let i = all.iter().position(|x| *x == self.difficulty).unwrap();
+                self.difficulty = all[cycle(i, all.len(), delta)];
+            }
+            MenuItem::Level => self.level = cycle(self.level, self.levels.len() + 1, delta),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn level_name(&self) -> Option<&str> {
+        match self.level {
+            0 => None,
+            i => Some(&self.levels[i - 1]),
+        }
+    }
+
+    pub fn config(&self) -> Result<GameConfig, String> {
+        let config = match self.level_name() {
+            Some(name) => GameConfig::with_level(Level::load(name)?, self.cell_size)?,
+            None => GameConfig::new(self.width, self.height, self.cell_size)?,
+        };
+        Ok(GameConfig {
+            players: self.players,
+            bots: self.bots,
+            bot_level: self.bot_level,
+            boundary: self.boundary,
+            difficulty: self.difficulty,
+            ..config
+        })
+    }
+}
+
+fn cycle(index: usize, len: usize, delta: i32) -> usize {
+    (index as i32 + delta).rem_euclid(len as i32) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_and_values_wrap_around() {
+        let mut menu = Menu::new(&GameConfig::default(), vec!["box".to_string()]);
+        menu.move_cursor(-1);
+        assert_eq!(menu.selected(), MenuItem::Quit);
+        assert!(!menu.change(1));
+
+        menu.cursor = 1;
+        assert!(menu.change(1));
+        assert_eq!(menu.players, 2);
+        menu.change(1);
+        assert_eq!(menu.players, 1);
+
+        menu.move_cursor(1);
+        menu.change(-1);
+        assert_eq!(menu.bots, BOTS_MAX);
+
+        menu.move_cursor(1);
+        menu.change(1);
+        assert_eq!(menu.bot_level, BotLevel::Hard);
+
+        menu.move_cursor(1);
+        assert!(menu.change(1));
+        assert_eq!(menu.boundary, Boundary::Walls);
+        menu.change(1);
+        assert_eq!(menu.boundary, Boundary::Wrap);
+
+        menu.move_cursor(1);
+        menu.change(-1);
+        assert_eq!(menu.difficulty, Difficulty::Easy);
+
+        menu.move_cursor(1);
+        assert_eq!(menu.level_name(), None);
+        menu.change(1);
+        assert_eq!(menu.level_name(), Some("box"));
+        menu.change(1);
+        assert_eq!(menu.level_name(), None);
+    }
+
+    #[test]
+    fn config_reflects_selection() {
+        let initial = GameConfig {
+            difficulty: Difficulty::Hard,
+            ..GameConfig::new(12, 10, 16).unwrap()
+        };
+        let mut menu = Menu::new(&initial, Level::builtin_names());
+        assert_eq!(menu.config().unwrap(), initial);
+
+        menu.players = 2;
+        menu.bots = 1;
+        menu.boundary = Boundary::Walls;
+        menu.level = menu.levels.iter().position(|x| x == "box").unwrap() + 1;
+        let config = menu.config().unwrap();
+        assert!(config.is_versus());
+        assert_eq!(config.snakes(), 3);
+        assert_eq!(config.boundary, Boundary::Walls);
+        assert_eq!(config.difficulty, Difficulty::Hard);
+        assert_eq!(config.cell_size, 16);
+        assert_eq!(config.level.unwrap().name, "box");
+    }
+
+    #[test]
+    fn starts_with_configured_level() {
+        let level = Level::load("resources/levels/cross.txt").unwrap();
+        let config = GameConfig::with_level(level, 20).unwrap();
+        let menu = Menu::new(&config, Level::builtin_names());
+        assert_eq!(menu.level_name(), Some("resources/levels/cross.txt"));
+        assert_eq!(menu.config().unwrap(), config);
+    }
+}
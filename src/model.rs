@@ -1,18 +1,239 @@
+use crate::bot::{self, BotLevel};
+use crate::level::Level;
+use crate::scoring::Scoring;
 use rand::prelude::*;
-use std::{collections::HashMap, time};
+use std::{
+    collections::{HashMap, VecDeque},
+    time,
+};
 
-pub const SCREEN_WIDTH: i32 = 420;
-pub const SCREEN_HEIGHT: i32 = 420;
-pub const CELL_SIZE: i32 = 20;
 pub const INFO_HEIGHT: i32 = 20;
-pub const CELLS_X_LEN: i32 = SCREEN_WIDTH / CELL_SIZE;
-pub const CELLS_X_MIN: i32 = 0;
-pub const CELLS_X_MAX: i32 = CELLS_X_LEN - 1;
-pub const CELLS_Y_LEN: i32 = (SCREEN_HEIGHT - INFO_HEIGHT) / CELL_SIZE;
-pub const CELLS_Y_MIN: i32 = 0;
-pub const CELLS_Y_MAX: i32 = CELLS_Y_LEN - 1;
+pub const BOARD_MIN_LEN: i32 = 5;
+pub const BOARD_MAX_LEN: i32 = 200;
 pub const ENERGY_MAX: i32 = 100;
+pub const TURN_QUEUE_MAX: usize = 3;
+pub const FOOD_SPAWN_INTERVAL: i32 = 30;
+pub const FOODS_MAX: usize = 5;
+pub const POO_SPAWN_DELAY: i32 = 60;
+pub const PLAYERS_MAX: usize = 2;
+pub const BOTS_MAX: usize = 3;
 
+// 盤面の端の扱い
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Boundary {
+    // 反対側に回り込む
+    Wrap,
+    // 外に出るとゲームオーバー
+    Walls,
+}
+
+impl Boundary {
+    pub fn all() -> Vec<Self> {
+        vec![Boundary::Wrap, Boundary::Walls]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Boundary::Wrap => "wrap",
+            Boundary::Walls => "walls",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().into_iter().find(|x| x.name() == name)
+    }
+}
+
+// 移動間隔の変化のしかた。長くなるほど速くなる
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpeedCurve {
+    // 最初の移動間隔 (フレーム)
+    pub start_interval: i32,
+    // これ以上は速くならない
+    pub min_interval: i32,
+    // 体がこの長さ伸びるごとにスピードレベルが1上がる
+    pub length_per_level: i32,
+    // スピードレベルが1上がるごとに縮む移動間隔
+    pub interval_step: i32,
+}
+
+impl SpeedCurve {
+    pub fn level(&self, length: usize) -> i32 {
+        length as i32 / self.length_per_level
+    }
+
+    pub fn interval(&self, level: i32) -> i32 {
+        (self.start_interval - level * self.interval_step).max(self.min_interval)
+    }
+
+    // 最高速度に達するスピードレベル
+    pub fn max_level(&self) -> i32 {
+        (self.start_interval - self.min_interval + self.interval_step - 1) / self.interval_step
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+    Insane,
+}
+
+impl Difficulty {
+    pub fn all() -> Vec<Self> {
+        vec![
+            Difficulty::Easy,
+            Difficulty::Normal,
+            Difficulty::Hard,
+            Difficulty::Insane,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+            Difficulty::Insane => "insane",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().into_iter().find(|x| x.name() == name)
+    }
+
+    pub fn speed_curve(&self) -> SpeedCurve {
+        let (start_interval, min_interval, length_per_level, interval_step) = match self {
+            Difficulty::Easy => (10, 5, 6, 1),
+            Difficulty::Normal => (8, 3, 5, 1),
+            Difficulty::Hard => (6, 2, 4, 1),
+            Difficulty::Insane => (4, 1, 3, 1),
+        };
+        SpeedCurve {
+            start_interval,
+            min_interval,
+            length_per_level,
+            interval_step,
+        }
+    }
+}
+
+// 盤面の大きさ。マス数と1マスのピクセル数
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameConfig {
+    pub width: i32,
+    pub height: i32,
+    pub cell_size: i32,
+    pub boundary: Boundary,
+    pub level: Option<Level>,
+    pub difficulty: Difficulty,
+    // 人のプレイヤーの数。2以上なら対戦
+    pub players: usize,
+    // コンピューターが操作するヘビの数と強さ
+    pub bots: usize,
+    pub bot_level: BotLevel,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            width: 21,
+            height: 20,
+            cell_size: 20,
+            boundary: Boundary::Wrap,
+            level: None,
+            difficulty: Difficulty::Normal,
+            players: 1,
+            bots: 0,
+            bot_level: BotLevel::Normal,
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn new(width: i32, height: i32, cell_size: i32) -> Result<Self, String> {
+        for (name, len) in [("width", width), ("height", height)] {
+            if !(BOARD_MIN_LEN..=BOARD_MAX_LEN).contains(&len) {
+                return Err(format!(
+                    "board {} must be between {} and {}: {}",
+                    name, BOARD_MIN_LEN, BOARD_MAX_LEN, len
+                ));
+            }
+        }
+        if cell_size < 1 {
+            return Err(format!("cell size must be positive: {}", cell_size));
+        }
+        Ok(GameConfig {
+            width,
+            height,
+            cell_size,
+            boundary: Boundary::Wrap,
+            level: None,
+            difficulty: Difficulty::Normal,
+            players: 1,
+            bots: 0,
+            bot_level: BotLevel::Normal,
+        })
+    }
+
+    // 盤面の大きさはステージに合わせる
+    pub fn with_level(level: Level, cell_size: i32) -> Result<Self, String> {
+        let config = Self::new(level.width, level.height, cell_size)?;
+        Ok(GameConfig {
+            level: Some(level),
+            ..config
+        })
+    }
+
+    pub fn is_versus(&self) -> bool {
+        self.players > 1
+    }
+
+    // 盤面にいるヘビの数
+    pub fn snakes(&self) -> usize {
+        self.players + self.bots
+    }
+
+    pub fn screen_width(&self) -> i32 {
+        self.width * self.cell_size
+    }
+
+    pub fn screen_height(&self) -> i32 {
+        self.height * self.cell_size + INFO_HEIGHT
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (0..self.width).contains(&x) && (0..self.height).contains(&y)
+    }
+
+    // 盤面の外に出た座標を反対側に回り込ませる
+    pub fn wrap(&self, x: i32, y: i32) -> Point {
+        Point {
+            x: x.rem_euclid(self.width),
+            y: y.rem_euclid(self.height),
+        }
+    }
+
+    // 端の扱いに従って座標を盤面内に収める。壁の外ならNone
+    pub fn resolve(&self, x: i32, y: i32) -> Option<Point> {
+        match self.boundary {
+            Boundary::Wrap => Some(self.wrap(x, y)),
+            Boundary::Walls if self.contains(x, y) => Some(Point { x, y }),
+            Boundary::Walls => None,
+        }
+    }
+
+    pub fn is_wall(&self, p: &Point) -> bool {
+        self.level.as_ref().is_some_and(|level| level.is_wall(p))
+    }
+
+    pub fn all_points(&self) -> impl Iterator<Item = Point> + '_ {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| Point { x, y }))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Command {
     None,
     Left,
@@ -21,7 +242,7 @@ pub enum Command {
     Up,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Direction {
     Left,
     Right,
@@ -38,6 +259,26 @@ impl Direction {
             Direction::Up => Direction::Down,
         }
     }
+
+    // 1マス進んだときのxとyの変化
+    pub fn delta(&self) -> (i32, i32) {
+        match self {
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+            Direction::Down => (0, 1),
+            Direction::Up => (0, -1),
+        }
+    }
+
+    // 上向きから時計回りの角度
+    pub fn angle(&self) -> f32 {
+        match self {
+            Direction::Left => 270.0,
+            Direction::Right => 90.0,
+            Direction::Down => 180.0,
+            Direction::Up => 0.0,
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -66,20 +307,75 @@ impl FoodColor {
             FoodColor::Blue => 5,
         }
     }
+
+    pub fn points(&self) -> i32 {
+        match self {
+            FoodColor::White => 0,
+            FoodColor::Red => 40,
+            FoodColor::Yellow => 20,
+            FoodColor::Blue => 10,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FoodColor::White => "white",
+            FoodColor::Red => "red",
+            FoodColor::Yellow => "yellow",
+            FoodColor::Blue => "blue",
+        }
+    }
 }
 
+// ヘビが倒れた原因。同じフレームで複数あれば先に判定したもの
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DeathCause {
+    // 画面端やステージの壁
+    Wall,
+    // 自分の体をかじった
+    SelfBite,
+    Poo,
+    Starvation,
+    // 他のヘビとぶつかった
+    Collision,
+}
+
+impl DeathCause {
+    pub fn all() -> Vec<Self> {
+        vec![
+            DeathCause::Wall,
+            DeathCause::SelfBite,
+            DeathCause::Poo,
+            DeathCause::Starvation,
+            DeathCause::Collision,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeathCause::Wall => "wall",
+            DeathCause::SelfBite => "self",
+            DeathCause::Poo => "poo",
+            DeathCause::Starvation => "starvation",
+            DeathCause::Collision => "collision",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Food {
     pub color: FoodColor,
     pub p: Point,
     pub is_exist: bool,
 }
 
+#[derive(Clone, Debug)]
 pub struct Poo {
     pub p: Point,
     pub is_exist: bool,
 }
 
-#[derive(Debug, Default, Clone, Eq, PartialEq)]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
 pub struct Point {
     pub x: i32,
     pub y: i32,
@@ -87,38 +383,64 @@ pub struct Point {
 
 impl Point {
     pub fn new(x: i32, y: i32) -> Self {
-        Point {
-            x: (CELLS_X_LEN + x) % CELLS_X_LEN,
-            y: (CELLS_Y_LEN + y) % CELLS_Y_LEN,
-        }
+        Point { x, y }
     }
 
-    pub fn neighbor(&self, direction: Direction) -> Self {
+    // 隣のマス。壁に囲まれた盤面の外に出る場合はNone
+    pub fn neighbor(&self, direction: Direction, config: &GameConfig) -> Option<Self> {
         match direction {
-            Direction::Left => Self::new(self.x - 1, self.y),
-            Direction::Right => Self::new(self.x + 1, self.y),
-            Direction::Up => Self::new(self.x, self.y - 1),
-            Direction::Down => Self::new(self.x, self.y + 1),
+            Direction::Left => config.resolve(self.x - 1, self.y),
+            Direction::Right => config.resolve(self.x + 1, self.y),
+            Direction::Up => config.resolve(self.x, self.y - 1),
+            Direction::Down => config.resolve(self.x, self.y + 1),
         }
     }
 }
 
+#[derive(Clone, Debug)]
 pub struct Player {
     pub p: Point,
     pub direction: Direction,
     pub bodies: Vec<Point>,
     pub energy: i32,
+    pub is_dead: bool,
+    pub death: Option<DeathCause>,
+    pub score: i32,
+    pub scoring: Scoring,
+    pub ate_counts: HashMap<FoodColor, i32>,
+    pub ate_count: i32,
+    pub poo_spawn_frame: i32,
+    pub next_move_frame: i32,
+    // 最後に移動したフレームと、その前の頭と体の位置 ([頭, 体...]の順)。なめらかに描くのに使う
+    pub last_move_frame: i32,
+    pub last_positions: Vec<Point>,
+    pub turn_queue: VecDeque<Direction>,
 }
 
 impl Player {
-    pub fn new() -> Self {
-        let player = Player {
-            p: Point::new(CELLS_X_LEN / 2, CELLS_Y_LEN / 2),
-            direction: Direction::Up,
+    pub fn new(config: &GameConfig) -> Self {
+        let (p, direction) = start_positions(config).swap_remove(0);
+        Self::at(p, direction)
+    }
+
+    pub fn at(p: Point, direction: Direction) -> Self {
+        Player {
+            p,
+            direction,
             bodies: Vec::new(),
             energy: ENERGY_MAX,
-        };
-        player
+            is_dead: false,
+            death: None,
+            score: 0,
+            scoring: Scoring::default(),
+            ate_counts: FoodColor::all().into_iter().map(|x| (x, 0)).collect(),
+            ate_count: 0,
+            poo_spawn_frame: -1,
+            next_move_frame: 0,
+            last_move_frame: 0,
+            last_positions: Vec::new(),
+            turn_queue: VecDeque::new(),
+        }
     }
 
     pub fn set_direction(&mut self, direction: Direction) {
@@ -126,18 +448,20 @@ impl Player {
     }
 
     pub fn get_angle(&self) -> f32 {
-        match self.direction {
-            Direction::Left => 270.0,
-            Direction::Right => 90.0,
-            Direction::Down => 180.0,
-            Direction::Up => 0.0,
-        }
+        self.direction.angle()
     }
 
-    pub fn do_move(&mut self) {
+    // 移動する。壁にぶつかった場合はその場に留まりfalseを返す
+    pub fn do_move(&mut self, config: &GameConfig) -> bool {
         self.energy -= 1;
+        self.last_positions = self.positions();
 
-        if self.bodies.len() >= 1 {
+        let next = match self.p.neighbor(self.direction, config) {
+            Some(next) => next,
+            None => return false,
+        };
+
+        if !self.bodies.is_empty() {
             let mut i = self.bodies.len() - 1;
             while i >= 1 {
                 self.bodies[i] = self.bodies[i - 1].clone();
@@ -147,175 +471,379 @@ impl Player {
             self.bodies[0] = self.p.clone();
         }
 
-        self.p = self.p.neighbor(self.direction.clone());
+        self.p = next;
+        true
     }
 
-    pub fn grow(&mut self) {
-        let new_pos = match self.bodies.len() {
-            0 => self.p.neighbor(self.direction.opposite()),
-            1 => {
-                let direction = get_direction(self.p.clone(), self.bodies[0].clone());
-                self.bodies[0].neighbor(direction)
-            }
-            _ => {
-                let direction = get_direction(
-                    self.bodies[self.bodies.len() - 2].clone(),
-                    self.bodies[self.bodies.len() - 1].clone(),
-                );
-                let last_pos = self.bodies.last().unwrap();
-                last_pos.neighbor(direction)
-            }
+    // 頭と体の位置。[頭, 体...]の順
+    pub fn positions(&self) -> Vec<Point> {
+        let mut positions = vec![self.p.clone()];
+        positions.extend(self.bodies.iter().cloned());
+        positions
+    }
+
+    // 頭 (0) と体 (1から) の移動前の位置。移動のあとで伸びた体は今の位置
+    pub fn last_position(&self, i: usize) -> Point {
+        match self.last_positions.get(i) {
+            Some(p) => p.clone(),
+            None if i == 0 => self.p.clone(),
+            None => self.bodies[i - 1].clone(),
+        }
+    }
+
+    // 前の移動から次の移動までの進み具合。0で移動前の位置、1で今の位置にいる
+    // timeはフレーム数で、フレームの途中は小数で表す
+    pub fn move_phase(&self, time: f32) -> f32 {
+        let interval = self.next_move_frame - self.last_move_frame;
+        if self.is_dead || interval <= 0 {
+            return 1.0;
+        }
+        ((time - self.last_move_frame as f32) / interval as f32).clamp(0.0, 1.0)
+    }
+
+    pub fn grow(&mut self, config: &GameConfig) {
+        let last_pos = self.bodies.last().unwrap_or(&self.p).clone();
+        let direction = match self.bodies.len() {
+            0 => Some(self.direction.opposite()),
+            1 => get_direction(self.p.clone(), self.bodies[0].clone()),
+            _ => get_direction(
+                self.bodies[self.bodies.len() - 2].clone(),
+                self.bodies[self.bodies.len() - 1].clone(),
+            ),
         };
+        // 壁際で伸ばす場所がない場合は尻尾に重ねておく。移動するとほどける
+        let new_pos = direction
+            .and_then(|direction| last_pos.neighbor(direction, config))
+            .unwrap_or(last_pos);
         self.bodies.push(new_pos);
     }
 
     pub fn shrink(&mut self) {
         self.bodies.pop();
     }
+
+    // 食べ物を食べる。鳴らす音を返す
+    fn eat(&mut self, color: &FoodColor, frame: i32, config: &GameConfig) -> &'static str {
+        *self.ate_counts.entry(color.clone()).or_insert(0) += 1;
+        self.scoring.on_eat(color, frame, self.energy);
+        if *color == FoodColor::White {
+            self.shrink();
+            return "shrink.wav";
+        }
+        self.energy = clamp(0, self.energy + color.energy(), ENERGY_MAX);
+        self.grow(config);
+        self.ate_count += 1;
+        if self.ate_count % 3 == 0 {
+            self.poo_spawn_frame = frame + POO_SPAWN_DELAY; // 指定フレームにうんこを生み出す
+        }
+        "eat.wav"
+    }
+
+    // 移動1回につき方向転換は1回まで。続けて押された方向は次以降の移動のために溜めておく
+    // 同じ向き・真後ろ・溜めすぎで捨てたときはfalse
+    fn queue_turn(&mut self, direction: Direction) -> bool {
+        let last = self.turn_queue.back().copied().unwrap_or(self.direction);
+        if direction == last || self.is_reversal(last, direction) {
+            return false;
+        }
+        if self.turn_queue.len() >= TURN_QUEUE_MAX {
+            return false;
+        }
+        self.turn_queue.push_back(direction);
+        true
+    }
+
+    fn apply_queued_turn(&mut self) {
+        if let Some(direction) = self.turn_queue.pop_front() {
+            if !self.is_reversal(self.direction, direction) {
+                self.set_direction(direction);
+            }
+        }
+    }
+
+    // 体があるときに真後ろを向くと自分の首をかじってしまう
+    fn is_reversal(&self, from: Direction, to: Direction) -> bool {
+        !self.bodies.is_empty() && to == from.opposite()
+    }
+
+    // 体の長さに応じたスピードレベル
+    pub fn speed_level(&self, config: &GameConfig) -> i32 {
+        config.difficulty.speed_curve().level(self.bodies.len())
+    }
+
+    // 今の移動間隔 (フレーム)
+    pub fn move_interval(&self, config: &GameConfig) -> i32 {
+        config
+            .difficulty
+            .speed_curve()
+            .interval(self.speed_level(config))
+    }
+}
+
+// ヘビごとの最初の位置と向き。人のプレイヤー、コンピューターの順
+// ステージがなければ横に等間隔に並べる。ステージでは2人目を左右対称の位置に置く
+fn start_positions(config: &GameConfig) -> Vec<(Point, Direction)> {
+    let count = config.snakes();
+    let (first, direction) = match &config.level {
+        Some(level) => (level.start.clone(), level.direction),
+        None => (start_column(config, 0, count), Direction::Up),
+    };
+    let mut points = vec![first];
+    for i in 1..count {
+        let candidate = match &config.level {
+            Some(_) if i == 1 => Point::new(config.width - 1 - points[0].x, points[0].y),
+            Some(_) => farthest_point(config, &points),
+            None => start_column(config, i, count),
+        };
+        // 壁の中や他のヘビと同じ位置になる場合は一番離れたマス
+        let p = if points.contains(&candidate) || config.is_wall(&candidate) {
+            farthest_point(config, &points)
+        } else {
+            candidate
+        };
+        points.push(p);
+    }
+    points.into_iter().map(|p| (p, direction)).collect()
+}
+
+// count匹を左右対称に並べたときのi匹目の位置
+fn start_column(config: &GameConfig, i: usize, count: usize) -> Point {
+    let (i, count) = (i as i32, count as i32);
+    let x = ((config.width - 1) * (i + 1) * 2 + count + 1) / ((count + 1) * 2);
+    Point::new(x, config.height / 2)
 }
 
-// p1からp2への向きを返す
-fn get_direction(p1: Point, p2: Point) -> Direction {
-    if p1.x < p2.x {
-        Direction::Right
-    } else if p1.x > p2.x {
-        Direction::Left
-    } else if p1.y < p2.y {
-        Direction::Down
+// 壁以外で、pointsのどれからも一番離れたマス
+fn farthest_point(config: &GameConfig, points: &[Point]) -> Point {
+    config
+        .all_points()
+        .filter(|x| !config.is_wall(x) && !points.contains(x))
+        .max_by_key(|x| {
+            points
+                .iter()
+                .map(|p| (x.x - p.x).abs() + (x.y - p.y).abs())
+                .min()
+        })
+        .unwrap_or_else(|| points[0].clone())
+}
+
+// プレイヤーごとに押された順の操作を、押された順番ごとの並びに直す。足りない所はCommand::None
+pub fn command_rounds(inputs: &[Vec<Command>]) -> Vec<Vec<Command>> {
+    let len = inputs.iter().map(|x| x.len()).max().unwrap_or(0);
+    (0..len)
+        .map(|k| {
+            inputs
+                .iter()
+                .map(|x| x.get(k).copied().unwrap_or(Command::None))
+                .collect()
+        })
+        .collect()
+}
+
+// p1から隣のp2への向き。盤面の端をまたぐ回り込みも隣とみなす。同じマスならNone
+pub fn get_direction(p1: Point, p2: Point) -> Option<Direction> {
+    let dx = p2.x - p1.x;
+    let dy = p2.y - p1.y;
+    if dx == 1 || dx < -1 {
+        Some(Direction::Right)
+    } else if dx == -1 || dx > 1 {
+        Some(Direction::Left)
+    } else if dy == 1 || dy < -1 {
+        Some(Direction::Down)
+    } else if dy == -1 || dy > 1 {
+        Some(Direction::Up)
     } else {
-        Direction::Up
+        None
     }
 }
 
 pub struct Game {
     pub rng: StdRng,
+    pub seed: u64,
+    pub config: GameConfig,
     pub is_over: bool,
+    pub is_cleared: bool,
     pub frame: i32,
-    pub player: Player,
-    pub score: i32,
+    // 人のプレイヤーのあとにコンピューターのヘビが並ぶ
+    pub players: Vec<Player>,
+    // players[config.players + i]を操作する
+    pub bots: Vec<Box<dyn bot::Strategy>>,
+    // 対戦で最後まで生き残ったプレイヤー。相打ちならNone
+    pub winner: Option<usize>,
     pub requested_sounds: Vec<&'static str>,
-    pub ate_counts: HashMap<FoodColor, i32>,
     pub foods: Vec<Food>,
     pub poos: Vec<Poo>,
-    pub poo_spawn_frame: i32,
-    pub ate_count: i32,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Game {
     pub fn new() -> Self {
-        let now = time::SystemTime::now();
-        let timestamp = now
-            .duration_since(time::UNIX_EPOCH)
-            .expect("SystemTime before UNIX EPOCH!")
-            .as_secs();
-        let rng = StdRng::seed_from_u64(timestamp);
+        Self::with_seed(time_seed())
+    }
+
+    // 同じseedなら同じ展開になる
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_config(GameConfig::default(), seed)
+    }
+
+    pub fn with_config(config: GameConfig, seed: u64) -> Self {
+        let rng = StdRng::seed_from_u64(seed);
+
+        let players = start_positions(&config)
+            .into_iter()
+            .map(|(p, direction)| {
+                let mut player = Player::at(p, direction);
+                player.next_move_frame = player.move_interval(&config);
+                player
+            })
+            .collect();
+        let bots = (0..config.bots)
+            .map(|_| config.bot_level.strategy())
+            .collect();
 
         let mut game = Game {
-            rng: rng,
+            rng,
+            seed,
+            config,
             is_over: false,
+            is_cleared: false,
             frame: 0,
-            player: Player::new(),
-            score: 0,
+            players,
+            bots,
+            winner: None,
             requested_sounds: Vec::new(),
-            ate_counts: HashMap::new(),
             foods: Vec::new(),
             poos: Vec::new(),
-            poo_spawn_frame: -1,
-            ate_count: 0,
         };
 
-        for color in FoodColor::all() {
-            game.ate_counts.insert(color, 0);
+        for p in game.config.all_points() {
+            game.foods.push(Food {
+                color: FoodColor::Red,
+                p,
+                is_exist: false,
+            });
         }
 
-        for y in CELLS_Y_MIN..=CELLS_Y_MAX {
-            for x in CELLS_X_MIN..=CELLS_X_MAX {
-                game.foods.push(Food {
-                    color: FoodColor::Red,
-                    p: Point { x: x, y: y },
-                    is_exist: false,
-                });
-            }
-        }
-
-        for y in CELLS_Y_MIN..=CELLS_Y_MAX {
-            for x in CELLS_X_MIN..=CELLS_X_MAX {
-                game.poos.push(Poo {
-                    p: Point { x: x, y: y },
-                    is_exist: false,
-                });
-            }
+        for p in game.config.all_points() {
+            game.poos.push(Poo { p, is_exist: false });
         }
 
         game
     }
 
+    // 1人用。対戦ではupdate_playersを使う
     pub fn update(&mut self, command: Command) {
+        self.update_players(&[command]);
+    }
+
+    // 1フレームの間に押された操作をすべて順に受け付けてから1フレーム進める
+    // rounds[k]はk番目に押された操作をプレイヤーごとに並べたもの (command_roundsで作る)
+    pub fn update_rounds(&mut self, rounds: &[Vec<Command>]) {
+        match rounds.split_last() {
+            Some((last, earlier)) => {
+                for commands in earlier {
+                    self.queue_commands(commands);
+                }
+                self.update_players(last);
+            }
+            None => self.update_players(&[]),
+        }
+    }
+
+    // commands[i]をi番目のヘビの曲がる操作として溜める。フレームは進めない
+    pub fn queue_commands(&mut self, commands: &[Command]) {
         if self.is_over {
             return;
         }
-
-        match command {
-            Command::None => {}
-            Command::Left => {
-                self.player.set_direction(Direction::Left);
-                self.requested_sounds.push("e4.wav");
+        for (i, (player, command)) in self.players.iter_mut().zip(commands).enumerate() {
+            if player.is_dead {
+                continue;
             }
-            Command::Right => {
-                self.player.set_direction(Direction::Right);
-                self.requested_sounds.push("d4.wav");
-            }
-            Command::Down => {
-                self.player.set_direction(Direction::Down);
-                self.requested_sounds.push("a4.wav");
-            }
-            Command::Up => {
-                self.player.set_direction(Direction::Up);
-                self.requested_sounds.push("g4.wav");
+            let (direction, sound) = match command {
+                Command::None => continue,
+                Command::Left => (Direction::Left, "e4.wav"),
+                Command::Right => (Direction::Right, "d4.wav"),
+                Command::Down => (Direction::Down, "a4.wav"),
+                Command::Up => (Direction::Up, "g4.wav"),
+            };
+            // 受け付けなかった操作と、コンピューターが曲がる音は鳴らさない
+            if player.queue_turn(direction) && i < self.config.players {
+                self.requested_sounds.push(sound);
             }
         }
+    }
+
+    // commands[i]がi番目のプレイヤーの操作。足りない分はCommand::None
+    // コンピューターのヘビの操作はここで決める
+    pub fn update_players(&mut self, commands: &[Command]) {
+        if self.is_over {
+            return;
+        }
+
+        let mut commands = commands.to_vec();
+        commands.resize(self.config.players, Command::None);
+        commands.extend(self.bot_commands());
+        self.queue_commands(&commands);
 
-        if self.frame != 0 && self.frame % 8 == 0 {
-            self.player.do_move();
+        // このフレームでぶつかったプレイヤー
+        let mut crashed: Vec<Option<DeathCause>> = vec![None; self.players.len()];
+        // 移動前の頭の位置。すれ違いの判定に使う
+        let last_heads: Vec<Point> = self.players.iter().map(|x| x.p.clone()).collect();
+
+        for (i, player) in self.players.iter_mut().enumerate() {
+            if player.is_dead || self.frame != player.next_move_frame {
+                continue;
+            }
+            player.last_move_frame = self.frame;
+            player.next_move_frame = self.frame + player.move_interval(&self.config);
+            player.apply_queued_turn();
+            if !player.do_move(&self.config) {
+                crashed[i] = Some(DeathCause::Wall);
+            }
         }
 
-        if self.frame != 0 && self.frame % 30 == 0 && self.foods_count() < 5 {
-            self.spawn_food();
+        if self.frame != 0 && self.frame % FOOD_SPAWN_INTERVAL == 0 {
+            if self.is_filled() {
+                // 盤面がヘビで埋まったらクリア
+                self.is_over = true;
+                self.is_cleared = true;
+                self.requested_sounds.push("pi.wav");
+            } else if self.foods_count() < FOODS_MAX {
+                self.spawn_food();
+            }
         }
 
-        if self.frame == self.poo_spawn_frame {
-            self.spawn_poo();
+        for i in 0..self.players.len() {
+            if !self.players[i].is_dead && self.frame == self.players[i].poo_spawn_frame {
+                self.spawn_poo(i);
+            }
         }
 
-        for food in &mut self.foods {
+        for (i, player) in self.players.iter_mut().enumerate() {
+            if player.is_dead || crashed[i].is_some() {
+                continue;
+            }
+            let food = &mut self.foods[(player.p.y * self.config.width + player.p.x) as usize];
             if food.is_exist {
-                if food.p == self.player.p {
-                    food.is_exist = false;
-                    self.ate_counts
-                        .insert(food.color.clone(), self.ate_counts[&food.color] + 1);
-                    if food.color == FoodColor::White {
-                        self.player.shrink();
-                        self.requested_sounds.push("shrink.wav");
-                    } else {
-                        self.player.energy =
-                            clamp(0, self.player.energy + food.color.energy(), ENERGY_MAX);
-                        self.player.grow();
-                        self.ate_count += 1;
-                        if self.ate_count % 3 == 0 {
-                            self.poo_spawn_frame = self.frame + 60; // 指定フレームにうんこを生み出す
-                        }
-                        self.requested_sounds.push("eat.wav");
-                    }
-                }
+                food.is_exist = false;
+                let sound = player.eat(&food.color, self.frame, &self.config);
+                self.requested_sounds.push(sound);
             }
         }
 
+        // どちらが出したうんこでも踏んだら負け
         for poo in &mut self.poos {
             if poo.is_exist {
-                if poo.p == self.player.p {
-                    poo.is_exist = false;
-                    self.is_over = true;
-                    self.requested_sounds.push("crash.wav");
+                for (i, player) in self.players.iter().enumerate() {
+                    if !player.is_dead && poo.p == player.p {
+                        poo.is_exist = false;
+                        crashed[i].get_or_insert(DeathCause::Poo);
+                    }
                 }
 
                 // うんこと重なっている食べ物は消す
@@ -327,35 +855,146 @@ impl Game {
             }
         }
 
-        for body in &self.player.bodies {
-            if *body == self.player.p {
-                self.is_over = true;
+        for (i, player) in self.players.iter().enumerate() {
+            if player.is_dead {
+                continue;
+            }
+            let cause = if self.config.is_wall(&player.p) {
+                Some(DeathCause::Wall)
+            } else if player.bodies.contains(&player.p) {
+                Some(DeathCause::SelfBite)
+            } else if player.energy < 0 {
+                Some(DeathCause::Starvation)
+            } else {
+                None
+            };
+            if let Some(cause) = cause {
+                crashed[i].get_or_insert(cause);
+            }
+
+            // 他のヘビとの衝突。前のフレームまでに負けたヘビは盤面から消えている
+            for (j, other) in self.players.iter().enumerate() {
+                if i == j || other.is_dead {
+                    continue;
+                }
+                // 頭同士の正面衝突とすれ違いは相打ち
+                let head_on =
+                    player.p == other.p || (player.p == last_heads[j] && other.p == last_heads[i]);
+                if head_on || other.bodies.contains(&player.p) {
+                    crashed[i].get_or_insert(DeathCause::Collision);
+                }
+            }
+        }
+
+        for (i, player) in self.players.iter_mut().enumerate() {
+            if let Some(cause) = crashed[i] {
+                player.is_dead = true;
+                player.death = Some(cause);
                 self.requested_sounds.push("crash.wav");
             }
         }
 
-        if self.player.energy < 0 {
+        self.frame += 1;
+        for player in &mut self.players {
+            player.scoring.on_length_changed(player.bodies.len());
+            player.score = player.scoring.total();
+        }
+
+        // コンピューターの勝ち負けはゲームの終わりに関係しない
+        let alive: Vec<usize> = (0..self.config.players)
+            .filter(|i| !self.players[*i].is_dead)
+            .collect();
+        if self.config.is_versus() {
+            // 対戦は残りが1人以下になったら終わり
+            if alive.len() <= 1 {
+                self.is_over = true;
+                self.winner = alive.first().copied();
+            }
+        } else if alive.is_empty() {
             self.is_over = true;
-            self.requested_sounds.push("crash.wav");
         }
+    }
 
-        self.frame += 1;
-        self.score = self.frame / 30;
+    // コンピューターのヘビは自分が動くフレームにだけ考える
+    fn bot_commands(&mut self) -> Vec<Command> {
+        let mut bots = std::mem::take(&mut self.bots);
+        let commands = bots
+            .iter_mut()
+            .enumerate()
+            .map(|(i, bot)| {
+                let index = self.config.players + i;
+                bot::command_on_move(bot.as_mut(), self, index)
+            })
+            .collect();
+        self.bots = bots;
+        commands
+    }
+
+    // 画面に出ている食べ物
+    pub fn existing_foods(&self) -> impl Iterator<Item = &Food> {
+        self.foods.iter().filter(|x| x.is_exist)
+    }
+
+    // 画面に出ているうんこ
+    pub fn existing_poos(&self) -> impl Iterator<Item = &Poo> {
+        self.poos.iter().filter(|x| x.is_exist)
+    }
+
+    // 鳴らすべき音を取り出す。ヘッドレスで回す場合も呼ばないと溜まり続ける
+    pub fn take_sounds(&mut self) -> Vec<&'static str> {
+        std::mem::take(&mut self.requested_sounds)
     }
 
     fn foods_count(&self) -> usize {
-        self.foods.iter().filter(|x| x.is_exist).count()
+        self.existing_foods().count()
     }
 
-    fn spawn_food(&mut self) {
-        let i: usize = self.rng.gen_range(0..self.foods.len());
-        if self.foods[i].is_exist {
-            return;
+    // 生きているヘビと壁のマス。with_itemsなら食べ物とうんこのマスも入れる
+    fn occupied_cells(&self, with_items: bool) -> Vec<bool> {
+        let width = self.config.width;
+        let mut occupied = vec![false; (width * self.config.height) as usize];
+        let mut occupy = |p: &Point| occupied[(p.y * width + p.x) as usize] = true;
+        for player in self.players.iter().filter(|x| !x.is_dead) {
+            occupy(&player.p);
+            player.bodies.iter().for_each(&mut occupy);
+        }
+        if with_items {
+            self.existing_foods().for_each(|x| occupy(&x.p));
+            self.existing_poos().for_each(|x| occupy(&x.p));
         }
-        self.foods[i].is_exist = true;
+        if let Some(level) = &self.config.level {
+            level.walls.iter().for_each(&mut occupy);
+        }
+        occupied
+    }
+
+    // 壁・体・うんこ・食べ物のどれもないマス
+    pub fn free_cells(&self) -> Vec<Point> {
+        let width = self.config.width;
+        let occupied = self.occupied_cells(true);
+        self.config
+            .all_points()
+            .filter(|p| !occupied[(p.y * width + p.x) as usize])
+            .collect()
+    }
+
+    // 壁以外のマスがすべて生きているヘビで埋まっている。食べ物やうんこは数えない
+    pub fn is_filled(&self) -> bool {
+        self.occupied_cells(false).into_iter().all(|x| x)
+    }
+
+    // 空いているマスから一様に選んで食べ物を出す。出せる場所がなければNone
+    pub fn spawn_food(&mut self) -> Option<Point> {
+        let mut cells = self.free_cells();
+        if let Some(level) = &self.config.level {
+            cells.retain(|p| level.allows_food(p));
+        }
+        let p = cells.choose(&mut self.rng)?.clone();
 
         let r: i32 = self.rng.gen_range(0..100);
-        self.foods[i].color = if r < 40 {
+        let food = &mut self.foods[(p.y * self.config.width + p.x) as usize];
+        food.is_exist = true;
+        food.color = if r < 40 {
             FoodColor::Blue
         } else if r < 75 {
             FoodColor::Yellow
@@ -364,15 +1003,20 @@ impl Game {
         } else {
             FoodColor::White
         };
+        Some(p)
     }
 
-    fn spawn_poo(&mut self) {
-        let pos;
-        if self.player.bodies.len() > 0 {
-            pos = self.player.bodies.last().unwrap().clone();
+    fn spawn_poo(&mut self, index: usize) {
+        let player = &self.players[index];
+        let pos = if !player.bodies.is_empty() {
+            player.bodies.last().unwrap().clone()
         } else {
-            pos = self.player.p.neighbor(self.player.direction.opposite());
-        }
+            // 後ろが壁なら生み出さない
+            match player.p.neighbor(player.direction.opposite(), &self.config) {
+                Some(pos) => pos,
+                None => return,
+            }
+        };
 
         for poo in &mut self.poos {
             if !poo.is_exist {
@@ -384,6 +1028,14 @@ impl Game {
     }
 }
 
+// seedを指定しない場合は現在時刻を使う
+pub fn time_seed() -> u64 {
+    let now = time::SystemTime::now();
+    now.duration_since(time::UNIX_EPOCH)
+        .expect("SystemTime before UNIX EPOCH!")
+        .as_secs()
+}
+
 fn clamp<T: PartialOrd>(min: T, value: T, max: T) -> T {
     if value < min {
         return min;
@@ -393,3 +1045,803 @@ fn clamp<T: PartialOrd>(min: T, value: T, max: T) -> T {
     }
     value
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // 次にプレイヤーが動くフレームまで進める
+    fn advance_one_move(game: &mut Game) {
+        loop {
+            let moving = game.frame == game.players[0].next_move_frame;
+            game.update(Command::None);
+            if game.is_over || moving {
+                break;
+            }
+        }
+    }
+
+    fn place_food(game: &mut Game, p: Point, color: FoodColor) {
+        let food = game.foods.iter_mut().find(|x| x.p == p).unwrap();
+        food.is_exist = true;
+        food.color = color;
+    }
+
+    fn is_adjacent(p1: &Point, p2: &Point, config: &GameConfig) -> bool {
+        let dx = (p1.x - p2.x).abs();
+        let dy = (p1.y - p2.y).abs();
+        let dx = dx.min(config.width - dx);
+        let dy = dy.min(config.height - dy);
+        dx + dy == 1
+    }
+
+    fn assert_no_gaps(player: &Player, config: &GameConfig) {
+        let mut prev = &player.p;
+        for body in &player.bodies {
+            assert!(is_adjacent(prev, body, config), "{:?} -> {:?}", prev, body);
+            prev = body;
+        }
+    }
+
+    #[test]
+    fn point_wraps_negative_coordinates() {
+        let config = GameConfig::default();
+        assert_eq!(
+            config.wrap(-1, -1),
+            Point::new(config.width - 1, config.height - 1)
+        );
+    }
+
+    #[test]
+    fn point_wraps_overflowing_coordinates() {
+        let config = GameConfig::default();
+        assert_eq!(config.wrap(config.width, config.height), Point::new(0, 0));
+        assert_eq!(
+            config.wrap(config.width + 3, config.height * 2 + 2),
+            Point::new(3, 2)
+        );
+    }
+
+    #[test]
+    fn neighbor_wraps_at_edges() {
+        let config = GameConfig::default();
+        let p = Point::new(0, 0);
+        assert_eq!(
+            p.neighbor(Direction::Left, &config).unwrap(),
+            Point::new(config.width - 1, 0)
+        );
+        assert_eq!(
+            p.neighbor(Direction::Up, &config).unwrap(),
+            Point::new(0, config.height - 1)
+        );
+    }
+
+    #[test]
+    fn neighbor_reports_walls() {
+        let config = GameConfig {
+            boundary: Boundary::Walls,
+            ..GameConfig::default()
+        };
+        let p = Point::new(0, 0);
+        assert_eq!(p.neighbor(Direction::Left, &config), None);
+        assert_eq!(p.neighbor(Direction::Up, &config), None);
+        assert_eq!(
+            p.neighbor(Direction::Right, &config),
+            Some(Point::new(1, 0))
+        );
+        let corner = Point::new(config.width - 1, config.height - 1);
+        assert_eq!(corner.neighbor(Direction::Right, &config), None);
+        assert_eq!(corner.neighbor(Direction::Down, &config), None);
+    }
+
+    #[test]
+    fn wrap_follows_config() {
+        let config = GameConfig::new(7, 5, 10).unwrap();
+        assert_eq!(config.wrap(7, 5), Point::new(0, 0));
+        assert_eq!(config.wrap(-1, -1), Point::new(6, 4));
+        assert_eq!(config.screen_width(), 70);
+        assert_eq!(config.screen_height(), 50 + INFO_HEIGHT);
+    }
+
+    #[test]
+    fn config_rejects_tiny_boards() {
+        assert!(GameConfig::new(BOARD_MIN_LEN - 1, 10, 20).is_err());
+        assert!(GameConfig::new(10, 10, 0).is_err());
+    }
+
+    #[test]
+    fn pools_follow_board_size() {
+        let game = Game::with_config(GameConfig::new(8, 6, 20).unwrap(), 0);
+        assert_eq!(game.foods.len(), 48);
+        assert_eq!(game.poos.len(), 48);
+        assert_eq!(game.players[0].p, Point::new(4, 3));
+    }
+
+    #[test]
+    fn grow_without_bodies_adds_segment_behind_head() {
+        let config = GameConfig::default();
+        let mut player = Player::new(&config);
+        player.grow(&config);
+        assert_eq!(
+            player.bodies,
+            vec![player.p.neighbor(Direction::Down, &config).unwrap()]
+        );
+    }
+
+    #[test]
+    fn grow_with_one_body_extends_away_from_head() {
+        let config = GameConfig::default();
+        let mut player = Player::new(&config);
+        player.grow(&config);
+        player.grow(&config);
+        let below = player.p.neighbor(Direction::Down, &config).unwrap();
+        assert_eq!(
+            player.bodies,
+            vec![
+                below.clone(),
+                below.neighbor(Direction::Down, &config).unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn grow_with_many_bodies_extends_tail() {
+        let config = GameConfig::default();
+        let mut player = Player::new(&config);
+        player.p = Point::new(5, 5);
+        player.direction = Direction::Right;
+        player.bodies = vec![Point::new(4, 5), Point::new(4, 6)];
+        player.grow(&config);
+        assert_eq!(player.bodies[2], Point::new(4, 7));
+        assert_no_gaps(&player, &config);
+    }
+
+    #[test]
+    fn grow_across_wrap_seam() {
+        let config = GameConfig::default();
+        let mut player = Player::new(&config);
+        player.p = Point::new(1, 5);
+        player.direction = Direction::Right;
+        player.bodies = vec![Point::new(0, 5)];
+        player.grow(&config);
+        assert_eq!(player.bodies[1], Point::new(config.width - 1, 5));
+        player.grow(&config);
+        assert_eq!(player.bodies[2], Point::new(config.width - 2, 5));
+        assert_no_gaps(&player, &config);
+
+        let config = GameConfig::default();
+        let mut player = Player::new(&config);
+        player.p = Point::new(5, config.height - 1);
+        player.direction = Direction::Up;
+        player.bodies = vec![Point::new(5, 0)];
+        player.grow(&config);
+        player.grow(&config);
+        assert_eq!(
+            player.bodies,
+            vec![Point::new(5, 0), Point::new(5, 1), Point::new(5, 2)]
+        );
+    }
+
+    #[test]
+    fn shrink_removes_tail() {
+        let config = GameConfig::default();
+        let mut player = Player::new(&config);
+        player.grow(&config);
+        player.grow(&config);
+        let first = player.bodies[0].clone();
+        player.shrink();
+        assert_eq!(player.bodies, vec![first]);
+        player.shrink();
+        player.shrink();
+        assert!(player.bodies.is_empty());
+    }
+
+    #[test]
+    fn do_move_drains_energy_and_body_follows() {
+        let config = GameConfig::default();
+        let mut player = Player::new(&config);
+        player.grow(&config);
+        let head = player.p.clone();
+        assert!(player.do_move(&config));
+        assert_eq!(player.energy, ENERGY_MAX - 1);
+        assert_eq!(player.p, head.neighbor(Direction::Up, &config).unwrap());
+        assert_eq!(player.bodies, vec![head]);
+    }
+
+    #[test]
+    fn do_move_keeps_last_positions() {
+        let config = GameConfig::default();
+        let mut player = Player::new(&config);
+        player.grow(&config);
+        let before = player.positions();
+        assert!(player.do_move(&config));
+        assert_eq!(player.last_positions, before);
+        assert_eq!(player.last_position(0), before[0]);
+        player.grow(&config);
+        // 移動のあとで伸びた体は動いていない
+        assert_eq!(player.last_position(2), player.bodies[1]);
+    }
+
+    #[test]
+    fn move_phase_runs_from_last_move_to_next() {
+        let mut game = Game::with_seed(0);
+        let interval = game.players[0].move_interval(&game.config);
+        advance_one_move(&mut game);
+        let player = &game.players[0];
+        assert_eq!(player.last_move_frame, game.frame - 1);
+        assert_eq!(player.next_move_frame, player.last_move_frame + interval);
+        assert_eq!(player.move_phase(player.last_move_frame as f32), 0.0);
+        assert_eq!(player.move_phase(game.frame as f32), 1.0 / interval as f32);
+        assert_eq!(player.move_phase(player.next_move_frame as f32), 1.0);
+        assert_eq!(player.move_phase(player.next_move_frame as f32 + 5.5), 1.0);
+        let half = player.last_move_frame as f32 + interval as f32 / 2.0;
+        assert_eq!(player.move_phase(half), 0.5);
+    }
+
+    #[test]
+    fn wrapped_neighbors_have_a_direction() {
+        let config = GameConfig::default();
+        let right = Point::new(config.width - 1, 3);
+        let left = Point::new(0, 3);
+        assert_eq!(
+            get_direction(right.clone(), left.clone()),
+            Some(Direction::Right)
+        );
+        assert_eq!(get_direction(left, right), Some(Direction::Left));
+        assert_eq!(Direction::Down.delta(), (0, 1));
+    }
+
+    #[test]
+    fn eating_food_grows_and_restores_energy() {
+        let mut game = Game::with_seed(0);
+        game.players[0].energy = 50;
+        let next = game.players[0]
+            .p
+            .neighbor(Direction::Up, &game.config)
+            .unwrap();
+        place_food(&mut game, next, FoodColor::Red);
+        advance_one_move(&mut game);
+        assert_eq!(game.players[0].bodies.len(), 1);
+        assert_eq!(game.players[0].energy, 50 - 1 + FoodColor::Red.energy());
+        assert_eq!(game.players[0].ate_counts[&FoodColor::Red], 1);
+    }
+
+    #[test]
+    fn score_comes_from_eating_not_idling() {
+        let mut game = Game::with_seed(0);
+        game.players[0].next_move_frame = i32::MAX;
+        for _ in 0..300 {
+            game.update(Command::None);
+        }
+        assert_eq!(game.players[0].score, 0);
+
+        game.players[0].next_move_frame = game.frame;
+        let next = game.players[0]
+            .p
+            .neighbor(Direction::Up, &game.config)
+            .unwrap();
+        place_food(&mut game, next, FoodColor::Red);
+        advance_one_move(&mut game);
+        let breakdown = &game.players[0].scoring.breakdown;
+        assert_eq!(breakdown.food, FoodColor::Red.points());
+        assert_eq!(breakdown.length, game.players[0].scoring.rules.length_bonus);
+        assert_eq!(game.players[0].score, breakdown.total());
+    }
+
+    #[test]
+    fn eating_white_food_shrinks() {
+        let mut game = Game::with_seed(0);
+        game.players[0].grow(&game.config);
+        game.players[0].grow(&game.config);
+        let next = game.players[0]
+            .p
+            .neighbor(Direction::Up, &game.config)
+            .unwrap();
+        place_food(&mut game, next, FoodColor::White);
+        advance_one_move(&mut game);
+        assert_eq!(game.players[0].bodies.len(), 1);
+        assert_eq!(game.players[0].ate_count, 0);
+    }
+
+    #[test]
+    fn poo_spawns_after_every_third_food() {
+        let mut game = Game::with_seed(0);
+        for i in 0..3 {
+            assert_eq!(game.existing_poos().count(), 0);
+            let next = game.players[0]
+                .p
+                .neighbor(Direction::Up, &game.config)
+                .unwrap();
+            place_food(&mut game, next, FoodColor::Blue);
+            advance_one_move(&mut game);
+            assert_eq!(game.players[0].ate_count, i + 1);
+        }
+        assert_eq!(
+            game.players[0].poo_spawn_frame,
+            game.frame - 1 + POO_SPAWN_DELAY
+        );
+
+        while game.frame <= game.players[0].poo_spawn_frame {
+            assert_eq!(game.existing_poos().count(), 0);
+            game.update(Command::None);
+        }
+        assert_eq!(game.existing_poos().count(), 1);
+    }
+
+    #[test]
+    fn dies_by_biting_itself() {
+        let mut game = Game::with_seed(0);
+        game.players[0].p = Point::new(5, 5);
+        game.players[0].direction = Direction::Up;
+        game.players[0].bodies = vec![
+            Point::new(5, 6),
+            Point::new(6, 6),
+            Point::new(6, 5),
+            Point::new(6, 4),
+            Point::new(5, 4),
+            Point::new(4, 4),
+        ];
+        advance_one_move(&mut game);
+        assert!(game.is_over);
+        assert_eq!(game.players[0].death, Some(DeathCause::SelfBite));
+        assert!(game.take_sounds().contains(&"crash.wav"));
+    }
+
+    #[test]
+    fn dies_by_hitting_poo() {
+        let mut game = Game::with_seed(0);
+        let next = game.players[0]
+            .p
+            .neighbor(Direction::Up, &game.config)
+            .unwrap();
+        game.poos[0].p = next;
+        game.poos[0].is_exist = true;
+        advance_one_move(&mut game);
+        assert!(game.is_over);
+        assert_eq!(game.players[0].death, Some(DeathCause::Poo));
+    }
+
+    #[test]
+    fn dies_by_hitting_wall() {
+        let config = GameConfig {
+            boundary: Boundary::Walls,
+            ..GameConfig::default()
+        };
+        let mut game = Game::with_config(config, 0);
+        game.players[0].p = Point::new(3, 0);
+        advance_one_move(&mut game);
+        assert!(game.is_over);
+        assert_eq!(game.players[0].death, Some(DeathCause::Wall));
+        assert_eq!(game.players[0].p, Point::new(3, 0));
+        assert!(game.take_sounds().contains(&"crash.wav"));
+    }
+
+    #[test]
+    fn wrap_mode_crosses_edge() {
+        let mut game = Game::with_seed(0);
+        game.players[0].p = Point::new(3, 0);
+        advance_one_move(&mut game);
+        assert!(!game.is_over);
+        assert_eq!(game.players[0].p, Point::new(3, game.config.height - 1));
+    }
+
+    #[test]
+    fn grow_against_wall_stacks_on_tail() {
+        let config = GameConfig {
+            boundary: Boundary::Walls,
+            ..GameConfig::default()
+        };
+        let mut player = Player::new(&config);
+        player.p = Point::new(3, 1);
+        player.bodies = vec![Point::new(3, 0)];
+        player.grow(&config);
+        player.grow(&config);
+        assert_eq!(player.bodies, vec![Point::new(3, 0); 3]);
+        player.direction = Direction::Right;
+        assert!(player.do_move(&config));
+        assert!(player.do_move(&config));
+        assert_eq!(
+            player.bodies,
+            vec![Point::new(4, 1), Point::new(3, 1), Point::new(3, 0)]
+        );
+    }
+
+    #[test]
+    fn dies_by_hitting_level_wall() {
+        let level = Level::parse("#####\n#...#\n#.S.#\n#...#\n#####\n", "test").unwrap();
+        let config = GameConfig::with_level(level, 20).unwrap();
+        let mut game = Game::with_config(config, 0);
+        assert_eq!(game.players[0].p, Point::new(2, 2));
+        advance_one_move(&mut game);
+        assert!(!game.is_over);
+        advance_one_move(&mut game);
+        assert!(game.is_over);
+    }
+
+    #[test]
+    fn food_never_spawns_in_level_walls() {
+        let level = Level::parse("#####\n#.*.#\n#.S.#\n#...#\n#####\n", "test").unwrap();
+        let config = GameConfig::with_level(level, 20).unwrap();
+        let mut game = Game::with_config(config, 0);
+        assert_eq!(game.spawn_food(), Some(Point::new(2, 1)));
+        assert_eq!(game.spawn_food(), None);
+        let foods: Vec<Point> = game.existing_foods().map(|f| f.p.clone()).collect();
+        assert_eq!(foods, vec![Point::new(2, 1)]);
+    }
+
+    #[test]
+    fn dies_by_starvation() {
+        let mut game = Game::with_seed(0);
+        game.players[0].energy = 0;
+        advance_one_move(&mut game);
+        assert!(game.is_over);
+        assert_eq!(game.players[0].death, Some(DeathCause::Starvation));
+    }
+
+    #[test]
+    fn reversal_into_neck_is_ignored() {
+        let mut game = Game::with_seed(0);
+        game.players[0].grow(&game.config);
+        game.update(Command::Down);
+        advance_one_move(&mut game);
+        assert!(!game.is_over);
+        assert_eq!(game.players[0].direction, Direction::Up);
+    }
+
+    #[test]
+    fn reversal_is_allowed_without_bodies() {
+        let mut game = Game::with_seed(0);
+        game.update(Command::Down);
+        advance_one_move(&mut game);
+        assert_eq!(game.players[0].direction, Direction::Down);
+    }
+
+    #[test]
+    fn quick_turns_are_applied_one_per_move() {
+        let mut game = Game::with_seed(0);
+        game.players[0].grow(&game.config);
+        game.update(Command::Left);
+        game.update(Command::Down);
+        advance_one_move(&mut game);
+        assert_eq!(game.players[0].direction, Direction::Left);
+        advance_one_move(&mut game);
+        assert_eq!(game.players[0].direction, Direction::Down);
+        assert!(!game.is_over);
+    }
+
+    #[test]
+    fn reversal_of_queued_turn_is_dropped() {
+        let mut game = Game::with_seed(0);
+        game.players[0].grow(&game.config);
+        game.update(Command::Left);
+        game.update(Command::Right);
+        game.update(Command::Left);
+        assert_eq!(game.players[0].turn_queue, vec![Direction::Left]);
+    }
+
+    #[test]
+    fn turns_pressed_within_one_frame_are_all_queued() {
+        let mut game = Game::with_seed(0);
+        game.players[0].grow(&game.config);
+        // 上向きのまま、1フレームの間に左、下と押す
+        let rounds = command_rounds(&[vec![Command::Left, Command::Down]]);
+        assert_eq!(rounds, vec![vec![Command::Left], vec![Command::Down]]);
+        game.update_rounds(&rounds);
+        let player = &game.players[0];
+        let mut turns: Vec<Direction> = player.turn_queue.iter().copied().collect();
+        if player.direction == Direction::Left {
+            turns.insert(0, Direction::Left);
+        }
+        assert_eq!(turns, vec![Direction::Left, Direction::Down]);
+        assert_eq!(
+            command_rounds(&[vec![Command::Up], vec![]]),
+            vec![vec![Command::Up, Command::None]]
+        );
+        assert!(command_rounds(&[vec![], vec![]]).is_empty());
+    }
+
+    #[test]
+    fn dropped_turns_make_no_sound() {
+        let mut game = Game::with_seed(0);
+        game.players[0].grow(&game.config);
+        let turn_sounds = |game: &mut Game, command: Command| {
+            game.update(command);
+            game.take_sounds()
+                .into_iter()
+                .filter(|x| ["e4.wav", "d4.wav", "a4.wav", "g4.wav"].contains(x))
+                .count()
+        };
+        assert_eq!(turn_sounds(&mut game, Command::Left), 1);
+        // 真後ろと、溜めてある向きと同じ向き
+        assert_eq!(turn_sounds(&mut game, Command::Right), 0);
+        assert_eq!(turn_sounds(&mut game, Command::Left), 0);
+    }
+
+    #[test]
+    fn food_spawns_only_on_free_cells() {
+        let config = GameConfig::new(5, 5, 20).unwrap();
+        let mut game = Game::with_config(config, 0);
+        game.players[0].grow(&game.config);
+        game.players[0].grow(&game.config);
+        game.poos[0].p = Point::new(0, 0);
+        game.poos[0].is_exist = true;
+
+        let free = game.free_cells().len();
+        assert_eq!(free, 25 - 3 - 1);
+        for _ in 0..free {
+            let p = game.spawn_food().unwrap();
+            assert_ne!(p, game.players[0].p);
+            assert!(!game.players[0].bodies.contains(&p));
+            assert_ne!(p, Point::new(0, 0));
+        }
+        assert_eq!(game.existing_foods().count(), free);
+        assert!(game.free_cells().is_empty());
+        assert_eq!(game.spawn_food(), None);
+    }
+
+    #[test]
+    fn filling_the_board_clears_the_game() {
+        let config = GameConfig::new(5, 5, 20).unwrap();
+        let mut game = Game::with_config(config.clone(), 0);
+        let head = game.players[0].p.clone();
+        game.players[0].bodies = config.all_points().filter(|p| *p != head).collect();
+        game.players[0].next_move_frame = i32::MAX;
+        game.players[0].energy = ENERGY_MAX;
+        while game.frame <= FOOD_SPAWN_INTERVAL {
+            game.update(Command::None);
+        }
+        assert!(game.is_over);
+        assert!(game.is_cleared);
+    }
+
+    #[test]
+    fn board_full_of_poo_is_not_cleared() {
+        let config = GameConfig::new(5, 5, 20).unwrap();
+        let mut game = Game::with_config(config, 0);
+        for poo in &mut game.poos {
+            poo.is_exist = poo.p != game.players[0].p;
+        }
+        game.players[0].next_move_frame = i32::MAX;
+        game.players[0].energy = ENERGY_MAX;
+        while game.frame <= FOOD_SPAWN_INTERVAL {
+            game.update(Command::None);
+        }
+        assert!(game.free_cells().is_empty());
+        assert!(!game.is_cleared);
+    }
+
+    #[test]
+    fn speed_curve_shrinks_interval_down_to_minimum() {
+        let curve = Difficulty::Normal.speed_curve();
+        assert_eq!(curve.level(0), 0);
+        assert_eq!(curve.level(4), 0);
+        assert_eq!(curve.level(5), 1);
+        assert_eq!(curve.interval(0), 8);
+        assert_eq!(curve.interval(1), 7);
+        assert_eq!(curve.interval(curve.max_level()), curve.min_interval);
+        assert_eq!(curve.interval(100), curve.min_interval);
+    }
+
+    #[test]
+    fn difficulty_presets_get_faster() {
+        let intervals: Vec<i32> = Difficulty::all()
+            .iter()
+            .map(|d| d.speed_curve().start_interval)
+            .collect();
+        assert!(intervals.windows(2).all(|w| w[0] > w[1]));
+        for difficulty in Difficulty::all() {
+            assert_eq!(Difficulty::from_name(difficulty.name()), Some(difficulty));
+        }
+    }
+
+    #[test]
+    fn longer_snake_moves_more_often() {
+        let mut game = Game::with_seed(0);
+        let start = game.frame;
+        advance_one_move(&mut game);
+        advance_one_move(&mut game);
+        assert_eq!(game.frame - start, 8 * 2 + 1);
+
+        for _ in 0..10 {
+            game.players[0].grow(&game.config);
+        }
+        assert_eq!(game.players[0].speed_level(&game.config), 2);
+        advance_one_move(&mut game);
+        let start = game.frame;
+        advance_one_move(&mut game);
+        assert_eq!(game.frame - start, 6);
+    }
+
+    fn versus_game() -> Game {
+        let config = GameConfig {
+            players: 2,
+            ..GameConfig::default()
+        };
+        Game::with_config(config, 0)
+    }
+
+    // 2人の頭と向きを置き直す
+    fn place_heads(game: &mut Game, heads: [(Point, Direction); 2]) {
+        for (player, (p, direction)) in game.players.iter_mut().zip(heads) {
+            player.p = p;
+            player.direction = direction;
+        }
+    }
+
+    #[test]
+    fn versus_players_start_apart() {
+        let game = versus_game();
+        assert_eq!(game.players.len(), 2);
+        assert_eq!(game.players[0].p, Point::new(7, 10));
+        assert_eq!(game.players[1].p, Point::new(13, 10));
+
+        let level = Level::parse("#####\n#...#\n#.S.#\n#...#\n#####\n", "test").unwrap();
+        let config = GameConfig {
+            players: 2,
+            ..GameConfig::with_level(level, 20).unwrap()
+        };
+        let game = Game::with_config(config, 0);
+        assert_eq!(game.players[0].p, Point::new(2, 2));
+        assert_ne!(game.players[1].p, game.players[0].p);
+        assert!(!game.config.is_wall(&game.players[1].p));
+    }
+
+    #[test]
+    fn head_on_collision_is_a_draw() {
+        let mut game = versus_game();
+        place_heads(
+            &mut game,
+            [
+                (Point::new(5, 5), Direction::Right),
+                (Point::new(7, 5), Direction::Left),
+            ],
+        );
+        advance_one_move(&mut game);
+        assert!(game.players.iter().all(|x| x.is_dead));
+        assert!(game.is_over);
+        assert_eq!(game.winner, None);
+    }
+
+    #[test]
+    fn swapping_heads_is_a_draw() {
+        let mut game = versus_game();
+        place_heads(
+            &mut game,
+            [
+                (Point::new(5, 5), Direction::Right),
+                (Point::new(6, 5), Direction::Left),
+            ],
+        );
+        advance_one_move(&mut game);
+        assert!(game.is_over);
+        assert_eq!(game.winner, None);
+    }
+
+    #[test]
+    fn hitting_other_body_loses() {
+        let mut game = versus_game();
+        place_heads(
+            &mut game,
+            [
+                (Point::new(9, 6), Direction::Right),
+                (Point::new(10, 5), Direction::Up),
+            ],
+        );
+        game.players[1].bodies = vec![Point::new(10, 6), Point::new(10, 7)];
+        advance_one_move(&mut game);
+        assert_eq!(game.players[0].death, Some(DeathCause::Collision));
+        assert!(!game.players[1].is_dead);
+        assert!(game.is_over);
+        assert_eq!(game.winner, Some(1));
+    }
+
+    #[test]
+    fn poo_of_other_snake_kills() {
+        let mut game = versus_game();
+        game.players[1].poo_spawn_frame = game.frame;
+        game.update_players(&[]);
+        let poo = game.existing_poos().next().unwrap().p.clone();
+        assert_eq!(poo, Point::new(13, 11));
+
+        place_heads(
+            &mut game,
+            [
+                (Point::new(13, 12), Direction::Up),
+                (Point::new(3, 3), Direction::Up),
+            ],
+        );
+        advance_one_move(&mut game);
+        assert!(game.players[0].is_dead);
+        assert_eq!(game.winner, Some(1));
+        assert_eq!(game.existing_poos().count(), 0);
+    }
+
+    #[test]
+    fn each_player_keeps_own_counts() {
+        let mut game = versus_game();
+        let next = game.players[1]
+            .p
+            .neighbor(Direction::Up, &game.config)
+            .unwrap();
+        place_food(&mut game, next, FoodColor::Red);
+        game.update_players(&[Command::Left, Command::None]);
+        advance_one_move(&mut game);
+        assert!(!game.is_over);
+        assert_eq!(game.players[0].direction, Direction::Left);
+        assert_eq!(game.players[0].ate_counts[&FoodColor::Red], 0);
+        assert_eq!(game.players[1].ate_counts[&FoodColor::Red], 1);
+        assert_eq!(game.players[1].bodies.len(), 1);
+        assert!(game.players[1].score > game.players[0].score);
+    }
+
+    #[test]
+    fn snakes_start_on_distinct_cells() {
+        for (players, bots) in [(1, BOTS_MAX), (PLAYERS_MAX, BOTS_MAX)] {
+            for width in [BOARD_MIN_LEN, 21] {
+                let config = GameConfig {
+                    players,
+                    bots,
+                    ..GameConfig::new(width, 10, 20).unwrap()
+                };
+                let game = Game::with_config(config, 0);
+                assert_eq!(game.players.len(), players + bots);
+                for (i, a) in game.players.iter().enumerate() {
+                    assert!(game.players[i + 1..].iter().all(|b| b.p != a.p));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dead_bot_leaves_the_board() {
+        let config = GameConfig {
+            bots: 1,
+            ..GameConfig::default()
+        };
+        let mut game = Game::with_config(config, 0);
+        game.players[1].energy = 0;
+        advance_one_move(&mut game);
+        assert!(game.players[1].is_dead);
+        assert!(!game.is_over);
+        assert!(game.free_cells().contains(&game.players[1].p));
+    }
+
+    #[test]
+    fn same_seed_gives_same_game() {
+        let mut a = Game::with_seed(7);
+        let mut b = Game::with_seed(7);
+        for _ in 0..600 {
+            a.update(Command::None);
+            b.update(Command::None);
+        }
+        let foods = |g: &Game| g.existing_foods().map(|f| f.p.clone()).collect::<Vec<_>>();
+        assert_eq!(foods(&a), foods(&b));
+    }
+
+    fn command_strategy() -> impl Strategy<Value = Command> {
+        prop_oneof![
+            8 => Just(Command::None),
+            1 => Just(Command::Left),
+            1 => Just(Command::Right),
+            1 => Just(Command::Up),
+            1 => Just(Command::Down),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn random_commands_keep_body_connected(
+            seed in any::<u64>(),
+            commands in prop::collection::vec(command_strategy(), 0..1000),
+        ) {
+            let mut game = Game::with_seed(seed);
+            for command in commands {
+                game.update(command);
+                game.take_sounds();
+                assert_no_gaps(&game.players[0], &game.config);
+                prop_assert!(game.players[0].energy <= ENERGY_MAX);
+            }
+        }
+    }
+}
@@ -0,0 +1,1576 @@
+use crate::font::{Align, Font};
+use crate::highscore::{mode_key, HighScore, NAME_MAX_LEN};
+use crate::menu::{Menu, MenuItem};
+use crate::model::{
+    get_direction, Boundary, Direction, FoodColor, Game, GameConfig, Player, Point, BOTS_MAX,
+    ENERGY_MAX, INFO_HEIGHT, PLAYERS_MAX,
+};
+use crate::scoring::ScoreBreakdown;
+use crate::sprite::{body_piece, body_sprite, Sprite};
+
+pub const WALL_THICKNESS: i32 = 2;
+pub const ATE_COUNT_WIDTH: i32 = 16;
+pub const ATE_COUNT_HEIGHT: i32 = 16;
+pub const INFO_MARGIN_TOP: i32 = 2;
+pub const WHITE_X: i32 = 150;
+pub const ATE_COUNT_AND_COLOR_WIDTH: i32 = 40;
+pub const SPEED_MARGIN_LEFT: i32 = 6;
+pub const MAX_ENERGY_WIDTH: i32 = 120;
+pub const TEXT_SCALE: i32 = 1;
+pub const TITLE_SCALE: i32 = 2;
+pub const LINE_HEIGHT: i32 = 12;
+pub const PLAYER_MARK_WIDTH: i32 = 6;
+pub const SCORE_DIGITS: i32 = 6;
+// 情報表示が狭いときでもこれだけは満腹度の棒に残す
+pub const MIN_ENERGY_WIDTH: i32 = 24;
+pub const ATE_COUNTS_MARGIN_LEFT: i32 = 8;
+// ヘビごとの体の色と満腹度の色。人のプレイヤー、コンピューターの順
+pub const PLAYER_COLORS: [Color; PLAYERS_MAX + BOTS_MAX] = [
+    Color::rgb(61, 122, 61),
+    Color::rgb(61, 61, 153),
+    Color::rgb(153, 61, 122),
+    Color::rgb(153, 122, 31),
+    Color::rgb(92, 92, 92),
+];
+pub const ENERGY_COLORS: [Color; PLAYERS_MAX] =
+    [Color::rgb(128, 255, 128), Color::rgb(128, 160, 255)];
+pub const WHITE: Color = Color::rgb(255, 255, 255);
+pub const BACKGROUND: Color = Color::rgb(154, 230, 192);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    // 255で不透明
+    pub a: u8,
+}
+
+impl Color {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b, a: 255 }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Color { r, g, b, a }
+    }
+}
+
+// 画面上の四角 (ピクセル)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        Rect { x, y, w, h }
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.x <= x && x < self.x + self.w && self.y <= y && y < self.y + self.h
+    }
+
+    // 両方を囲む四角
+    pub fn union(&self, other: Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.w).max(other.x + other.w);
+        let bottom = (self.y + self.h).max(other.y + other.h);
+        Rect::new(x, y, right - x, bottom - y)
+    }
+}
+
+// 描く先。SDL2のウィンドウやメモリ上のフレームバッファ
+// 配置の計算はこのモジュールで行い、描く先は言われた場所に描くだけ
+pub trait Renderer {
+    fn font(&self) -> &Font;
+    // 数字の画像の1文字分の幅と高さ
+    fn digit_size(&self) -> (i32, i32);
+    fn clear(&mut self, color: Color) -> Result<(), String>;
+    // 半透明の色は下の色と混ぜる
+    fn fill_rect(&mut self, rect: Rect, color: Color) -> Result<(), String>;
+    // 枠だけ描く
+    fn draw_rect(&mut self, rect: Rect, color: Color) -> Result<(), String>;
+    // 数字の画像のdigitの字を(x, y)から描く
+    fn draw_digit(&mut self, digit: u32, x: i32, y: i32) -> Result<(), String>;
+    // フォントのcの字を拡大率scaleで(x, y)から描く
+    fn draw_glyph(
+        &mut self,
+        c: char,
+        x: i32,
+        y: i32,
+        scale: i32,
+        color: Color,
+    ) -> Result<(), String>;
+    // index番目のヘビの頭をdirectionの向きにしてrectに描く
+    fn draw_head(&mut self, index: usize, direction: Direction, rect: Rect) -> Result<(), String>;
+    // rectの外には描かないようにする。Noneで元に戻す
+    fn set_clip(&mut self, rect: Option<Rect>) -> Result<(), String>;
+    // 絵が読み込めていないときはfalseで、単色で描く
+    fn has_sprites(&self) -> bool;
+    // 上向きの絵をdirectionの向きに回してrectに描く。色にtintを掛ける (WHITEでそのまま)
+    fn draw_sprite(
+        &mut self,
+        sprite: &Sprite,
+        direction: Direction,
+        rect: Rect,
+        tint: Color,
+    ) -> Result<(), String>;
+}
+
+// 1フレーム分の描く内容。ゲームは読むだけで変えない
+pub struct Scene<'a> {
+    pub game: &'a Game,
+    pub overlay: Overlay<'a>,
+    pub motion: Motion,
+    // 最後にゲームを進めたフレームから次のフレームまでの進み具合 (0〜1)。Smoothの補間に使う
+    pub alpha: f32,
+}
+
+// ヘビの動き方の見せ方。Smoothはマスの間も滑らかに動かす。ゲームの判定はどちらもマス単位
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Motion {
+    #[default]
+    Grid,
+    Smooth,
+}
+
+impl Motion {
+    pub fn all() -> Vec<Self> {
+        vec![Motion::Grid, Motion::Smooth]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Motion::Grid => "grid",
+            Motion::Smooth => "smooth",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().into_iter().find(|x| x.name() == name)
+    }
+}
+
+// 盤面の上に重ねる画面
+pub enum Overlay<'a> {
+    None,
+    Title(&'a Menu),
+    Demo,
+    // hintは操作説明
+    Paused(String),
+    GameOver(String),
+    // 入力中の名前
+    NameEntry(&'a str),
+    HighScores(&'a [HighScore]),
+}
+
+// 文字の色・大きさ・揃え方
+#[derive(Clone, Copy, Debug)]
+pub struct TextStyle {
+    pub color: Color,
+    pub scale: i32,
+    pub align: Align,
+}
+
+impl TextStyle {
+    pub fn new(color: Color) -> Self {
+        TextStyle {
+            color,
+            scale: TEXT_SCALE,
+            align: Align::Left,
+        }
+    }
+}
+
+pub fn render_scene(renderer: &mut dyn Renderer, scene: &Scene) -> Result<(), String> {
+    let game = scene.game;
+    render_game(renderer, game, scene.motion, scene.alpha)?;
+    match &scene.overlay {
+        Overlay::None => {}
+        Overlay::Title(menu) => render_title(renderer, game, menu)?,
+        Overlay::Demo => render_demo(renderer, game)?,
+        Overlay::Paused(hint) => render_paused(renderer, game, hint)?,
+        Overlay::GameOver(hint) => render_game_over(renderer, game, hint)?,
+        Overlay::NameEntry(name) => {
+            render_game_over(renderer, game, "")?;
+            render_name_entry(renderer, game, name)?;
+        }
+        Overlay::HighScores(scores) => render_high_scores(renderer, game, scores)?,
+    }
+    Ok(())
+}
+
+// 盤面のマスの画面上の位置
+pub fn cell_rect(config: &GameConfig, x: i32, y: i32) -> Rect {
+    board_cell_rect(config.cell_size, INFO_HEIGHT, x, y)
+}
+
+// 1マスがcell_sizeピクセルで、盤面がtopから始まるときのマスの位置
+fn board_cell_rect(cell_size: i32, top: i32, x: i32, y: i32) -> Rect {
+    Rect::new(x * cell_size, y * cell_size + top, cell_size, cell_size)
+}
+
+pub fn get_draw_color(food_color: &FoodColor) -> Color {
+    match food_color {
+        FoodColor::White => Color::rgb(209, 220, 230),
+        FoodColor::Red => Color::rgb(255, 128, 128),
+        FoodColor::Yellow => Color::rgb(255, 255, 128),
+        FoodColor::Blue => Color::rgb(128, 128, 255),
+    }
+}
+
+// 上を向いた頭の模様。目の位置と大きさはcell_size=20のときの見た目に合わせて拡大縮小する
+pub fn head_pattern(cell_size: i32, color: Color) -> Vec<(Rect, Color)> {
+    let scale = |v: i32| (v * cell_size / 20).max(1);
+    vec![
+        (Rect::new(0, 0, cell_size, cell_size), color),
+        // 白目
+        (Rect::new(scale(6), 0, scale(3), scale(3)), WHITE),
+        (
+            Rect::new(cell_size - scale(6) - 1, 0, scale(3), scale(3)),
+            WHITE,
+        ),
+        // 黒目
+        (
+            Rect::new(scale(6) + scale(2), 0, scale(2), scale(2)),
+            Color::rgb(0, 0, 0),
+        ),
+        (
+            Rect::new(cell_size - scale(6) - 1, 0, scale(2), scale(2)),
+            Color::rgb(0, 0, 0),
+        ),
+    ]
+}
+
+// 盤面と上部の情報表示
+pub fn render_game(
+    renderer: &mut dyn Renderer,
+    game: &Game,
+    motion: Motion,
+    alpha: f32,
+) -> Result<(), String> {
+    let config = &game.config;
+    let screen_width = config.screen_width();
+    let screen_height = config.screen_height();
+
+    renderer.clear(BACKGROUND)?;
+    render_board(renderer, game, config.cell_size, INFO_HEIGHT, motion, alpha)?;
+
+    // render walls
+    if config.boundary == Boundary::Walls {
+        for i in 0..WALL_THICKNESS {
+            renderer.draw_rect(
+                Rect::new(
+                    i,
+                    INFO_HEIGHT + i,
+                    screen_width - i * 2,
+                    screen_height - INFO_HEIGHT - i * 2,
+                ),
+                Color::rgb(64, 64, 64),
+            )?;
+        }
+    }
+
+    // render info
+    renderer.fill_rect(
+        Rect::new(0, 0, screen_width, INFO_HEIGHT),
+        Color::rgb(64, 64, 64),
+    )?;
+
+    if config.is_versus() {
+        // 左右に分けて1人ずつ表示する
+        let width = screen_width / config.players as i32;
+        for i in 0..config.players {
+            render_player_info(renderer, game, i, width * i as i32, width)?;
+        }
+        return Ok(());
+    }
+
+    let digit_width = renderer.digit_size().0;
+    let layout = InfoLayout::fit(0, screen_width, digit_width, MAX_ENERGY_WIDTH, true);
+    render_info(renderer, game, 0, &layout)
+}
+
+// 情報表示の1人分の配置。幅が足りないときは食べた数を右から減らし、次にスピードを省いて
+// 満腹度の棒を縮める。得点も入らないほど狭いときは満腹度の棒だけになる
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InfoLayout {
+    pub energy: Rect,
+    pub speed_x: Option<i32>,
+    // 食べた数の色と、色の四角のx
+    pub ate_counts: Vec<(FoodColor, i32)>,
+    pub score_x: Option<i32>,
+}
+
+impl InfoLayout {
+    // xから幅widthに、左から満腹度・スピード・食べた数 (with_ate_countsのとき)・得点を並べる
+    pub fn fit(
+        x: i32,
+        width: i32,
+        digit_width: i32,
+        max_energy_width: i32,
+        with_ate_counts: bool,
+    ) -> Self {
+        let score_width = digit_width * SCORE_DIGITS;
+        let speed_width = digit_width * 2;
+        let count_width = ATE_COUNT_WIDTH + 4 + digit_width * 3;
+        let count_stride = ATE_COUNT_AND_COLOR_WIDTH + ATE_COUNT_WIDTH + 4;
+        // 右から詰めていき、rightより左を空きとする
+        let mut right = x + width;
+        let score_x = (width >= score_width).then(|| {
+            right -= score_width + SPEED_MARGIN_LEFT;
+            right + SPEED_MARGIN_LEFT
+        });
+
+        let speed_space = speed_width + SPEED_MARGIN_LEFT;
+        let colors = if with_ate_counts {
+            FoodColor::all()
+        } else {
+            Vec::new()
+        };
+        let mut ate_counts = Vec::new();
+        for n in (1..=colors.len() as i32).rev() {
+            let block = count_stride * (n - 1) + count_width;
+            let used = block + ATE_COUNTS_MARGIN_LEFT + speed_space;
+            if right - x - used < MIN_ENERGY_WIDTH {
+                continue;
+            }
+            let start = (x + WHITE_X).min(right - block);
+            for (i, color) in colors.into_iter().take(n as usize).enumerate() {
+                ate_counts.push((color, start + count_stride * i as i32));
+            }
+            right = start - ATE_COUNTS_MARGIN_LEFT;
+            break;
+        }
+
+        let speed_x = (right - x - speed_space >= MIN_ENERGY_WIDTH).then(|| {
+            let speed_x = (x + max_energy_width + SPEED_MARGIN_LEFT).min(right - speed_width);
+            right = speed_x - SPEED_MARGIN_LEFT;
+            speed_x
+        });
+
+        let energy_width = (right - x).min(max_energy_width).max(0);
+        InfoLayout {
+            energy: Rect::new(x, 0, energy_width, INFO_HEIGHT),
+            speed_x,
+            ate_counts,
+            score_x,
+        }
+    }
+}
+
+// index番目のプレイヤーの満腹度・スピード・食べた数・得点をlayoutの位置に描く
+fn render_info(
+    renderer: &mut dyn Renderer,
+    game: &Game,
+    index: usize,
+    layout: &InfoLayout,
+) -> Result<(), String> {
+    let player = &game.players[index];
+    let energy = layout.energy;
+    renderer.fill_rect(energy, Color::rgb(32, 32, 32))?;
+    renderer.fill_rect(
+        Rect::new(
+            energy.x,
+            energy.y,
+            energy.w * player.energy.max(0) / ENERGY_MAX,
+            energy.h,
+        ),
+        ENERGY_COLORS[index],
+    )?;
+
+    if let Some(x) = layout.speed_x {
+        render_number(
+            renderer,
+            x,
+            INFO_MARGIN_TOP,
+            &format!("{0: >2}", player.speed_level(&game.config)),
+        )?;
+    }
+
+    for (color, x) in &layout.ate_counts {
+        renderer.fill_rect(
+            Rect::new(*x, INFO_MARGIN_TOP, ATE_COUNT_WIDTH, ATE_COUNT_HEIGHT),
+            get_draw_color(color),
+        )?;
+        render_number(
+            renderer,
+            x + ATE_COUNT_WIDTH + 4,
+            INFO_MARGIN_TOP,
+            &format!("{0: >3}", player.ate_counts[color]),
+        )?;
+    }
+
+    if let Some(x) = layout.score_x {
+        render_number(
+            renderer,
+            x,
+            INFO_MARGIN_TOP,
+            &format!("{:>1$}", player.score, SCORE_DIGITS as usize),
+        )?;
+    }
+    Ok(())
+}
+
+// 盤面の中身 (レベルの壁・食べ物・うんち・ヘビ)。背景と外周の壁は描かない
+// 1マスをcell_sizeピクセルにして、y=topから描く
+pub fn render_board(
+    renderer: &mut dyn Renderer,
+    game: &Game,
+    cell_size: i32,
+    top: i32,
+    motion: Motion,
+    alpha: f32,
+) -> Result<(), String> {
+    let config = &game.config;
+    let cell = |x: i32, y: i32| board_cell_rect(cell_size, top, x, y);
+
+    // render level walls
+    if let Some(level) = &config.level {
+        for wall in &level.walls {
+            renderer.fill_rect(cell(wall.x, wall.y), Color::rgb(96, 96, 96))?;
+        }
+    }
+
+    let sprites = renderer.has_sprites();
+
+    // render foods
+    for food in game.existing_foods() {
+        let rect = cell(food.p.x, food.p.y);
+        if sprites {
+            let sprite = Sprite::Food(food.color.clone());
+            renderer.draw_sprite(&sprite, Direction::Up, rect, WHITE)?;
+        } else {
+            renderer.fill_rect(rect, get_draw_color(&food.color))?;
+        }
+    }
+
+    // render poos
+    for poo in game.existing_poos() {
+        let rect = cell(poo.p.x, poo.p.y);
+        if sprites {
+            renderer.draw_sprite(&Sprite::Poo, Direction::Up, rect, WHITE)?;
+        } else {
+            renderer.fill_rect(rect, Color::rgb(92, 48, 28))?;
+        }
+    }
+
+    for (i, player) in game.players.iter().enumerate() {
+        // 負けたコンピューターのヘビは盤面から消える
+        if player.is_dead && i >= config.players {
+            continue;
+        }
+
+        if motion == Motion::Smooth {
+            render_smooth_snake(renderer, game, i, cell_size, top, alpha)?;
+            continue;
+        }
+
+        // render bodies
+        for (j, body) in player.bodies.iter().enumerate() {
+            let rect = cell(body.x, body.y);
+            if !sprites {
+                renderer.fill_rect(rect, PLAYER_COLORS[i])?;
+            } else if let Some((sprite, direction)) = body_sprite(player, j) {
+                renderer.draw_sprite(&sprite, direction, rect, PLAYER_COLORS[i])?;
+            }
+        }
+
+        // render head
+        renderer.draw_head(i, player.direction, cell(player.p.x, player.p.y))?;
+    }
+    Ok(())
+}
+
+// 移動前のマスから今のマスへ、移動の進み具合の分だけずらして描く
+// 体は次の体とのすき間ができないように今のマスまで伸ばす
+// 盤面の端を回り込むときは両側に分けて描き、盤面の外にははみ出さない
+fn render_smooth_snake(
+    renderer: &mut dyn Renderer,
+    game: &Game,
+    index: usize,
+    cell_size: i32,
+    top: i32,
+    alpha: f32,
+) -> Result<(), String> {
+    let config = &game.config;
+    let player = &game.players[index];
+    // game.frameは次に進めるフレームなので、いま見せるのはその1つ前からalphaだけ進んだ時点
+    let phase = player.move_phase(game.frame as f32 - 1.0 + alpha);
+    let board = Rect::new(0, top, config.width * cell_size, config.height * cell_size);
+    // i番目 (0が頭) の移動前のマスと、そこから進んだ位置
+    let moving = |i: usize, p: &Point| {
+        let from = player.last_position(i);
+        let (dx, dy) = match get_direction(from.clone(), p.clone()) {
+            Some(direction) => direction.delta(),
+            None => (0, 0),
+        };
+        let start = board_cell_rect(cell_size, top, from.x, from.y);
+        let offset = (cell_size as f32 * phase).round() as i32;
+        let current = Rect::new(
+            start.x + dx * offset,
+            start.y + dy * offset,
+            cell_size,
+            cell_size,
+        );
+        let to = Rect::new(
+            start.x + dx * cell_size,
+            start.y + dy * cell_size,
+            cell_size,
+            cell_size,
+        );
+        (current, to)
+    };
+
+    renderer.set_clip(Some(board))?;
+    if renderer.has_sprites() {
+        let offset = (cell_size as f32 * phase).round() as i32;
+        let cell = |p: &Point| board_cell_rect(cell_size, top, p.x, p.y);
+        render_sliding_sprites(renderer, player, index, board, offset, cell, |i, p| {
+            moving(i, p).0
+        })?;
+    } else {
+        for (i, body) in player.bodies.iter().enumerate() {
+            let (current, to) = moving(i + 1, body);
+            for rect in wrapped_rects(current.union(to), board) {
+                renderer.fill_rect(rect, PLAYER_COLORS[index])?;
+            }
+        }
+    }
+    let (head, _) = moving(0, &player.p);
+    for rect in wrapped_rects(head, board) {
+        renderer.draw_head(index, player.direction, rect)?;
+    }
+    renderer.set_clip(None)
+}
+
+// 絵で描く体を移動前のマスから滑らせる。offsetは進んだピクセル数
+// 先に今のマスのまだ体が届いていない所へそのマスの形を描いて、曲がり角の隙間を埋める
+fn render_sliding_sprites(
+    renderer: &mut dyn Renderer,
+    player: &Player,
+    index: usize,
+    board: Rect,
+    offset: i32,
+    cell: impl Fn(&Point) -> Rect,
+    current: impl Fn(usize, &Point) -> Rect,
+) -> Result<(), String> {
+    let tint = PLAYER_COLORS[index];
+    let mut slides = Vec::new();
+    for (i, body) in player.bodies.iter().enumerate() {
+        let prev = if i == 0 {
+            &player.p
+        } else {
+            &player.bodies[i - 1]
+        };
+        let front = get_direction(body.clone(), prev.clone());
+        let (Some(front), Some(direction)) = (
+            front,
+            get_direction(player.last_position(i + 1), body.clone()),
+        ) else {
+            // 動いていない体は今のマスに置く
+            if let Some((sprite, rotation)) = body_sprite(player, i) {
+                renderer.draw_sprite(&sprite, rotation, cell(body), tint)?;
+            }
+            continue;
+        };
+        let cell = cell(body);
+        let rest = cell.w - offset;
+        if rest > 0 {
+            let ahead = match direction {
+                Direction::Up => Rect::new(cell.x, cell.y, cell.w, rest),
+                Direction::Down => Rect::new(cell.x, cell.y + offset, cell.w, rest),
+                Direction::Left => Rect::new(cell.x, cell.y, rest, cell.h),
+                Direction::Right => Rect::new(cell.x + offset, cell.y, rest, cell.h),
+            };
+            let (sprite, rotation) = body_piece(front, Some(direction.opposite()));
+            renderer.set_clip(Some(ahead))?;
+            renderer.draw_sprite(&sprite, rotation, cell, tint)?;
+        }
+        // 滑っている間は進む向きにまっすぐな体。最後は尻尾
+        let is_tail = i + 1 == player.bodies.len();
+        let piece = body_piece(direction, (!is_tail).then(|| direction.opposite()));
+        slides.push((piece, current(i + 1, body)));
+    }
+    renderer.set_clip(Some(board))?;
+    for ((sprite, rotation), rect) in slides {
+        for rect in wrapped_rects(rect, board) {
+            renderer.draw_sprite(&sprite, rotation, rect, tint)?;
+        }
+    }
+    Ok(())
+}
+
+// 盤面からはみ出した四角を、反対側に回り込んだ分も含めて返す
+fn wrapped_rects(rect: Rect, board: Rect) -> Vec<Rect> {
+    let mut xs = vec![0];
+    if rect.x < board.x {
+        xs.push(board.w);
+    }
+    if rect.x + rect.w > board.x + board.w {
+        xs.push(-board.w);
+    }
+    let mut ys = vec![0];
+    if rect.y < board.y {
+        ys.push(board.h);
+    }
+    if rect.y + rect.h > board.y + board.h {
+        ys.push(-board.h);
+    }
+    let mut rects = Vec::new();
+    for dy in &ys {
+        for dx in &xs {
+            rects.push(Rect::new(rect.x + dx, rect.y + dy, rect.w, rect.h));
+        }
+    }
+    rects
+}
+
+// 対戦のときの1人分の情報。xから幅widthに色の印・満腹度・スピードレベル・得点を並べる
+pub fn render_player_info(
+    renderer: &mut dyn Renderer,
+    game: &Game,
+    index: usize,
+    x: i32,
+    width: i32,
+) -> Result<(), String> {
+    let digit_width = renderer.digit_size().0;
+    renderer.fill_rect(
+        Rect::new(x, 0, PLAYER_MARK_WIDTH.min(width), INFO_HEIGHT),
+        ENERGY_COLORS[index],
+    )?;
+    // 右端は隣のプレイヤーとの間をあける
+    let inner = width - PLAYER_MARK_WIDTH - SPEED_MARGIN_LEFT;
+    let layout = InfoLayout::fit(x + PLAYER_MARK_WIDTH, inner, digit_width, inner, false);
+    render_info(renderer, game, index, &layout)
+}
+
+pub fn render_game_over(
+    renderer: &mut dyn Renderer,
+    game: &Game,
+    hint: &str,
+) -> Result<(), String> {
+    let screen_width = game.config.screen_width();
+    let screen_height = game.config.screen_height();
+
+    let color = if game.is_cleared {
+        Color::rgba(255, 255, 0, 128)
+    } else {
+        Color::rgba(255, 0, 0, 128)
+    };
+    renderer.fill_rect(
+        Rect::new(0, INFO_HEIGHT, screen_width, screen_height - INFO_HEIGHT),
+        color,
+    )?;
+
+    let title = if game.is_cleared {
+        "CLEAR!".to_string()
+    } else if game.config.is_versus() {
+        match game.winner {
+            Some(i) => format!("{}P WINS!", i + 1),
+            None => "DRAW".to_string(),
+        }
+    } else {
+        "GAME OVER".to_string()
+    };
+    let style = TextStyle {
+        scale: TITLE_SCALE,
+        align: Align::Center,
+        ..TextStyle::new(WHITE)
+    };
+    render_text(
+        renderer,
+        screen_width / 2,
+        screen_height / 2 - LINE_HEIGHT * 3,
+        &style,
+        &title,
+    )?;
+
+    // 再現できるようにseedを表示する
+    let seedstr = game.seed.to_string();
+    let digit_width = renderer.digit_size().0;
+    render_number(
+        renderer,
+        (screen_width - seedstr.len() as i32 * digit_width) / 2,
+        screen_height / 2,
+        &seedstr,
+    )?;
+
+    let y = if game.config.is_versus() {
+        render_versus_results(renderer, game, screen_height / 2 + 24)?
+    } else {
+        render_score_breakdown(
+            renderer,
+            &game.players[0].scoring.breakdown,
+            screen_height / 2 + 24,
+        )?
+    };
+    render_text(renderer, 12, y + LINE_HEIGHT, &TextStyle::new(WHITE), hint)?;
+    Ok(())
+}
+
+pub fn render_paused(renderer: &mut dyn Renderer, game: &Game, hint: &str) -> Result<(), String> {
+    let center = game.config.screen_width() / 2;
+    let style = TextStyle {
+        align: Align::Center,
+        ..TextStyle::new(WHITE)
+    };
+    let y = render_panel(renderer, game)?;
+    render_text(
+        renderer,
+        center,
+        y + LINE_HEIGHT * 4,
+        &TextStyle {
+            scale: TITLE_SCALE,
+            ..style
+        },
+        "PAUSED",
+    )?;
+    render_text(renderer, center, y + LINE_HEIGHT * 7, &style, hint)?;
+    Ok(())
+}
+
+pub fn render_demo(renderer: &mut dyn Renderer, game: &Game) -> Result<(), String> {
+    let config = &game.config;
+    let center = config.screen_width() / 2;
+    let style = TextStyle {
+        align: Align::Center,
+        ..TextStyle::new(WHITE)
+    };
+    render_text(
+        renderer,
+        center,
+        INFO_HEIGHT + LINE_HEIGHT * 2,
+        &TextStyle {
+            scale: TITLE_SCALE,
+            ..style
+        },
+        "DEMO PLAY",
+    )?;
+    render_text(
+        renderer,
+        center,
+        config.screen_height() - LINE_HEIGHT * 3,
+        &style,
+        "PRESS ANY KEY",
+    )?;
+    Ok(())
+}
+
+// タイトル画面。背景には選んでいる盤面を表示する
+pub fn render_title(renderer: &mut dyn Renderer, game: &Game, menu: &Menu) -> Result<(), String> {
+    let config = &game.config;
+    let center = config.screen_width() / 2;
+    let style = TextStyle {
+        align: Align::Center,
+        ..TextStyle::new(WHITE)
+    };
+    renderer.fill_rect(
+        Rect::new(
+            0,
+            INFO_HEIGHT,
+            config.screen_width(),
+            config.screen_height() - INFO_HEIGHT,
+        ),
+        Color::rgba(0, 0, 0, 128),
+    )?;
+
+    let mut y = INFO_HEIGHT + LINE_HEIGHT * 2;
+    render_text(
+        renderer,
+        center,
+        y,
+        &TextStyle {
+            scale: TITLE_SCALE * 2,
+            ..style
+        },
+        "RUST SNAKE",
+    )?;
+    y += LINE_HEIGHT * 3;
+    render_text(
+        renderer,
+        center,
+        y,
+        &TextStyle {
+            scale: TITLE_SCALE,
+            ..style
+        },
+        "へびゲーム",
+    )?;
+    y += LINE_HEIGHT * 3;
+
+    for (i, item) in MenuItem::all().into_iter().enumerate() {
+        let label = match item {
+            MenuItem::Start => "START".to_string(),
+            MenuItem::Players => match menu.players {
+                1 => "PLAYERS: < 1P >".to_string(),
+                n => format!("PLAYERS: < {}P VS >", n),
+            },
+            MenuItem::Bots => format!("BOTS: < {} >", menu.bots),
+            MenuItem::BotLevel => format!("BOT LEVEL: < {} >", menu.bot_level.name()),
+            MenuItem::Boundary => format!("MODE: < {} >", menu.boundary.name()),
+            MenuItem::Difficulty => format!("DIFFICULTY: < {} >", menu.difficulty.name()),
+            MenuItem::Level => format!(
+                "LEVEL: < {} >",
+                match menu.level_name() {
+                    Some(name) => name.to_string(),
+                    None => format!("{}x{}", menu.width, menu.height),
+                }
+            ),
+            MenuItem::HighScores => "HIGH SCORES".to_string(),
+            MenuItem::Quit => "QUIT".to_string(),
+        };
+        let color = if i == menu.cursor {
+            Color::rgb(255, 255, 128)
+        } else {
+            style.color
+        };
+        render_text(
+            renderer,
+            center,
+            y,
+            &TextStyle { color, ..style },
+            &label.to_uppercase(),
+        )?;
+        y += LINE_HEIGHT * 2;
+    }
+    Ok(())
+}
+
+// 得点の内訳。次の行のyを返す
+pub fn render_score_breakdown(
+    renderer: &mut dyn Renderer,
+    breakdown: &ScoreBreakdown,
+    y: i32,
+) -> Result<i32, String> {
+    let label_style = TextStyle::new(WHITE);
+    let points_style = TextStyle {
+        align: Align::Right,
+        ..label_style
+    };
+    let points_x = 12 + renderer.font().text_width("HUNGRY    00000", TEXT_SCALE);
+    let rows = [
+        ("FOOD", breakdown.food),
+        ("COMBO", breakdown.combo),
+        ("LENGTH", breakdown.length),
+        ("HUNGRY", breakdown.hungry),
+        ("TOTAL", breakdown.total()),
+    ];
+    let mut y = y;
+    for (label, points) in rows {
+        render_text(renderer, 12, y, &label_style, label)?;
+        render_text(renderer, points_x, y, &points_style, &points.to_string())?;
+        y += LINE_HEIGHT;
+    }
+    Ok(y)
+}
+
+// 対戦の結果。1行に1人ずつ得点・長さ・食べた数を並べ、次の行のyを返す
+pub fn render_versus_results(
+    renderer: &mut dyn Renderer,
+    game: &Game,
+    y: i32,
+) -> Result<i32, String> {
+    let style = TextStyle::new(WHITE);
+    let right = TextStyle {
+        align: Align::Right,
+        ..style
+    };
+    let font = renderer.font();
+    let score_x = 12 + font.text_width("2P 000000", TEXT_SCALE);
+    let length_x = score_x + font.text_width(" LEN 000", TEXT_SCALE);
+    let counts_x = length_x + font.text_width("  ", TEXT_SCALE);
+    let square = font.height * TEXT_SCALE;
+    let mut y = y;
+    for (i, player) in game.players.iter().take(game.config.players).enumerate() {
+        render_text(
+            renderer,
+            12,
+            y,
+            &TextStyle::new(ENERGY_COLORS[i]),
+            &format!("{}P", i + 1),
+        )?;
+        render_text(renderer, score_x, y, &right, &player.score.to_string())?;
+        render_text(
+            renderer,
+            length_x,
+            y,
+            &right,
+            &format!("LEN {}", player.bodies.len() + 1),
+        )?;
+
+        let mut cx = counts_x;
+        for color in FoodColor::all() {
+            renderer.fill_rect(Rect::new(cx, y, square, square), get_draw_color(&color))?;
+            cx += square + 2;
+            let count = player.ate_counts[&color].to_string();
+            cx += render_text(renderer, cx, y, &style, &count)? + square;
+        }
+        y += LINE_HEIGHT;
+    }
+    Ok(y)
+}
+
+// 盤面を暗くする。中に書き始めるyを返す
+pub fn render_panel(renderer: &mut dyn Renderer, game: &Game) -> Result<i32, String> {
+    let config = &game.config;
+    renderer.fill_rect(
+        Rect::new(
+            0,
+            INFO_HEIGHT,
+            config.screen_width(),
+            config.screen_height() - INFO_HEIGHT,
+        ),
+        Color::rgba(0, 0, 0, 200),
+    )?;
+    Ok(INFO_HEIGHT + LINE_HEIGHT)
+}
+
+pub fn render_name_entry(
+    renderer: &mut dyn Renderer,
+    game: &Game,
+    name: &str,
+) -> Result<(), String> {
+    let style = TextStyle::new(WHITE);
+    let large = TextStyle {
+        scale: TITLE_SCALE,
+        ..style
+    };
+    let mut y = render_panel(renderer, game)?;
+    render_text(renderer, 12, y, &large, "NEW HIGH SCORE!")?;
+    y += LINE_HEIGHT * 3;
+    render_text(
+        renderer,
+        12,
+        y,
+        &style,
+        &format!("SCORE {}", game.players[0].score),
+    )?;
+    y += LINE_HEIGHT * 2;
+    render_text(
+        renderer,
+        12,
+        y,
+        &TextStyle {
+            color: Color::rgb(255, 255, 128),
+            ..large
+        },
+        &format!("NAME: {}_", name),
+    )?;
+    y += LINE_HEIGHT * 3;
+    render_text(renderer, 12, y, &style, "ENTER: SAVE  ESC: SKIP")?;
+    Ok(())
+}
+
+pub fn render_high_scores(
+    renderer: &mut dyn Renderer,
+    game: &Game,
+    scores: &[HighScore],
+) -> Result<(), String> {
+    let style = TextStyle::new(WHITE);
+    let mut y = render_panel(renderer, game)?;
+    render_text(
+        renderer,
+        12,
+        y,
+        &TextStyle {
+            scale: TITLE_SCALE,
+            ..style
+        },
+        "HIGH SCORES",
+    )?;
+    y += LINE_HEIGHT * 2;
+    render_text(
+        renderer,
+        12,
+        y,
+        &TextStyle {
+            color: Color::rgb(160, 160, 160),
+            ..style
+        },
+        &mode_key(&game.config).to_uppercase(),
+    )?;
+    y += LINE_HEIGHT * 2;
+
+    // 名前にかなが入ると幅が変わるので列ごとに位置を決めて描く
+    let font = renderer.font();
+    let rank_x = 12 + font.text_width("00", TEXT_SCALE);
+    let name_x = rank_x + font.text_width(" ", TEXT_SCALE);
+    let score_x = name_x
+        + font.text_width(&"あ".repeat(NAME_MAX_LEN), TEXT_SCALE)
+        + font.text_width(" 000000", TEXT_SCALE);
+    let length_x = score_x + font.text_width(" LEN 000", TEXT_SCALE);
+    let date_x = length_x + font.text_width(" ", TEXT_SCALE);
+    let counts_x = date_x + font.text_width("0000-00-00 ", TEXT_SCALE);
+    let square = font.height * TEXT_SCALE;
+    let right = TextStyle {
+        align: Align::Right,
+        ..style
+    };
+    for (i, x) in scores.iter().enumerate() {
+        render_text(renderer, rank_x, y, &right, &(i + 1).to_string())?;
+        render_text(renderer, name_x, y, &style, &x.name)?;
+        render_text(renderer, score_x, y, &right, &x.score.to_string())?;
+        render_text(renderer, length_x, y, &right, &format!("LEN {}", x.length))?;
+        render_text(renderer, date_x, y, &style, &x.date())?;
+
+        // 食べた数
+        let mut cx = counts_x;
+        for color in FoodColor::all() {
+            renderer.fill_rect(Rect::new(cx, y, square, square), get_draw_color(&color))?;
+            cx += square + 2;
+            let count = x.ate_counts.get(&color).copied().unwrap_or(0).to_string();
+            cx += render_text(renderer, cx, y, &style, &count)? + square;
+        }
+        y += LINE_HEIGHT;
+    }
+    if scores.is_empty() {
+        render_text(renderer, 12, y, &style, "NO RECORDS YET")?;
+    }
+    Ok(())
+}
+
+// (x, y) を基準にstyle.alignで揃えて描く。描いた幅を返す
+pub fn render_text(
+    renderer: &mut dyn Renderer,
+    x: i32,
+    y: i32,
+    style: &TextStyle,
+    text: &str,
+) -> Result<i32, String> {
+    let font = renderer.font();
+    let width = font.text_width(text, style.scale);
+    let left = x + style.align.offset(width);
+    let layout = font.layout(text);
+    for (c, dx) in layout {
+        renderer.draw_glyph(c, left + dx * style.scale, y, style.scale, style.color)?;
+    }
+    Ok(width)
+}
+
+// 数字の画像で描く。数字以外の文字は1文字分空ける
+pub fn render_number(
+    renderer: &mut dyn Renderer,
+    x: i32,
+    y: i32,
+    numstr: &str,
+) -> Result<(), String> {
+    let digit_width = renderer.digit_size().0;
+    let mut x = x;
+    for c in numstr.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            renderer.draw_digit(digit, x, y)?;
+        }
+        x += digit_width;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framebuffer::{Bitmap, Framebuffer};
+    use crate::model::{Food, Point, Poo, BOARD_MIN_LEN};
+    use std::path::Path;
+
+    const INFO_BACKGROUND: Color = Color::rgb(64, 64, 64);
+
+    fn draw(game: &Game, overlay: Overlay) -> Framebuffer {
+        let config = &game.config;
+        let mut fb = Framebuffer::new(config.screen_width(), config.screen_height());
+        render_scene(
+            &mut fb,
+            &Scene {
+                game,
+                overlay,
+                motion: Motion::Grid,
+                alpha: 1.0,
+            },
+        )
+        .unwrap();
+        fb
+    }
+
+    fn center(rect: Rect) -> (i32, i32) {
+        (rect.x + rect.w / 2, rect.y + rect.h / 2)
+    }
+
+    fn hud(fb: &Framebuffer) -> Bitmap {
+        fb.crop(Rect::new(0, 0, fb.width, INFO_HEIGHT))
+    }
+
+    // tests/golden/<name>と比べる。UPDATE_GOLDEN=1のときは書き直す
+    fn assert_golden(name: &str, actual: &Bitmap) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, actual.to_ppm()).unwrap();
+            return;
+        }
+        let bytes = std::fs::read(&path)
+            .unwrap_or_else(|e| panic!("{}: {} (run with UPDATE_GOLDEN=1)", path.display(), e));
+        let expected = Bitmap::parse_ppm(&bytes).unwrap();
+        if *actual != expected {
+            // 見比べられるように書き出しておく
+            let out = std::env::temp_dir().join(name);
+            std::fs::write(&out, actual.to_ppm()).unwrap();
+            panic!("{} differs from {}", out.display(), path.display());
+        }
+    }
+
+    fn hud_game() -> Game {
+        let mut game = Game::with_seed(0);
+        let player = &mut game.players[0];
+        player.energy = ENERGY_MAX / 2;
+        player.score = 1234;
+        player.ate_counts.insert(FoodColor::Red, 7);
+        player.ate_counts.insert(FoodColor::Blue, 12);
+        game
+    }
+
+    fn versus_game() -> Game {
+        let config = GameConfig {
+            players: 2,
+            ..GameConfig::default()
+        };
+        let mut game = Game::with_config(config, 0);
+        game.players[0].energy = ENERGY_MAX;
+        game.players[0].score = 56;
+        game.players[1].energy = ENERGY_MAX / 4;
+        game.players[1].score = 789;
+        game
+    }
+
+    #[test]
+    fn board_cells_are_drawn_in_place() {
+        let mut game = Game::with_seed(0);
+        let config = game.config.clone();
+        game.foods = vec![
+            Food {
+                color: FoodColor::Yellow,
+                p: Point::new(3, 4),
+                is_exist: true,
+            },
+            Food {
+                color: FoodColor::White,
+                p: Point::new(0, 0),
+                is_exist: true,
+            },
+        ];
+        game.poos = vec![Poo {
+            p: Point::new(20, 19),
+            is_exist: true,
+        }];
+        game.players[0].bodies = vec![Point::new(10, 11)];
+        let fb = draw(&game, Overlay::None);
+
+        let (x, y) = center(cell_rect(&config, 3, 4));
+        assert_eq!(fb.pixel(x, y), get_draw_color(&FoodColor::Yellow));
+        let (x, y) = center(cell_rect(&config, 0, 0));
+        assert_eq!(fb.pixel(x, y), get_draw_color(&FoodColor::White));
+        let (x, y) = center(cell_rect(&config, 20, 19));
+        assert_eq!(fb.pixel(x, y), Color::rgb(92, 48, 28));
+        let (x, y) = center(cell_rect(&config, 10, 11));
+        assert_eq!(fb.pixel(x, y), PLAYER_COLORS[0]);
+        let head = &game.players[0].p;
+        let (x, y) = center(cell_rect(&config, head.x, head.y));
+        assert_eq!(fb.pixel(x, y), PLAYER_COLORS[0]);
+        let (x, y) = center(cell_rect(&config, 5, 5));
+        assert_eq!(fb.pixel(x, y), BACKGROUND);
+        assert_eq!(fb.pixel(config.cell_size, INFO_HEIGHT), BACKGROUND);
+    }
+
+    // 1x1の絵で、絵ごとに違う色
+    fn sprite_color(sprite: &Sprite) -> Color {
+        let i = sprite.index() as u8;
+        Color::rgb(255 - i * 20, 100 + i * 10, 50 + i * 20)
+    }
+
+    fn sprite_sheet() -> Bitmap {
+        let pixels: Vec<Color> = Sprite::all().iter().map(sprite_color).collect();
+        Bitmap {
+            width: pixels.len() as i32,
+            height: 1,
+            pixels,
+        }
+    }
+
+    fn tinted(sprite: Sprite, tint: Color) -> Color {
+        let c = sprite_color(&sprite);
+        let mul = |c: u8, t: u8| (c as u32 * t as u32 / 255) as u8;
+        Color::rgb(mul(c.r, tint.r), mul(c.g, tint.g), mul(c.b, tint.b))
+    }
+
+    #[test]
+    fn sprites_replace_flat_cells() {
+        let mut game = Game::with_seed(0);
+        let config = game.config.clone();
+        game.foods = vec![Food {
+            color: FoodColor::Blue,
+            p: Point::new(3, 4),
+            is_exist: true,
+        }];
+        game.poos = vec![Poo {
+            p: Point::new(5, 5),
+            is_exist: true,
+        }];
+        game.players[0].p = Point::new(10, 10);
+        game.players[0].bodies = vec![Point::new(10, 11), Point::new(11, 11)];
+        let mut fb = Framebuffer::new(config.screen_width(), config.screen_height());
+        fb.set_sprites(sprite_sheet()).unwrap();
+        render_board(
+            &mut fb,
+            &game,
+            config.cell_size,
+            INFO_HEIGHT,
+            Motion::Grid,
+            1.0,
+        )
+        .unwrap();
+
+        let (x, y) = center(cell_rect(&config, 3, 4));
+        assert_eq!(fb.pixel(x, y), tinted(Sprite::Food(FoodColor::Blue), WHITE));
+        let (x, y) = center(cell_rect(&config, 5, 5));
+        assert_eq!(fb.pixel(x, y), tinted(Sprite::Poo, WHITE));
+        let (x, y) = center(cell_rect(&config, 10, 11));
+        assert_eq!(fb.pixel(x, y), tinted(Sprite::Corner, PLAYER_COLORS[0]));
+        let (x, y) = center(cell_rect(&config, 11, 11));
+        assert_eq!(fb.pixel(x, y), tinted(Sprite::Tail, PLAYER_COLORS[0]));
+    }
+
+    #[test]
+    fn walls_frame_the_board() {
+        let config = GameConfig {
+            boundary: Boundary::Walls,
+            ..GameConfig::default()
+        };
+        let fb = draw(&Game::with_config(config.clone(), 0), Overlay::None);
+        let (w, h) = (config.screen_width(), config.screen_height());
+        assert_eq!(fb.pixel(0, INFO_HEIGHT), INFO_BACKGROUND);
+        assert_eq!(fb.pixel(w - WALL_THICKNESS, h - 1), INFO_BACKGROUND);
+        assert_eq!(fb.pixel(WALL_THICKNESS, h / 2), BACKGROUND);
+    }
+
+    #[test]
+    fn energy_bar_follows_energy() {
+        let game = hud_game();
+        let fb = draw(&game, Overlay::None);
+        let full = InfoLayout::fit(
+            0,
+            game.config.screen_width(),
+            fb.digit_size().0,
+            MAX_ENERGY_WIDTH,
+            true,
+        )
+        .energy
+        .w;
+        let width = full / 2;
+        assert_eq!(fb.pixel(0, 10), ENERGY_COLORS[0]);
+        assert_eq!(fb.pixel(width - 1, 10), ENERGY_COLORS[0]);
+        assert_eq!(fb.pixel(width, 10), Color::rgb(32, 32, 32));
+        assert_eq!(fb.pixel(full, 10), INFO_BACKGROUND);
+    }
+
+    #[test]
+    fn info_layout_never_overlaps() {
+        let digit_width = Framebuffer::new(1, 1).digit_size().0;
+        for width in 0..=600 {
+            for with_ate_counts in [true, false] {
+                let layout =
+                    InfoLayout::fit(10, width, digit_width, MAX_ENERGY_WIDTH, with_ate_counts);
+                let mut spans = vec![(layout.energy.x, layout.energy.w)];
+                spans.extend(layout.speed_x.map(|x| (x, digit_width * 2)));
+                spans.extend(
+                    layout
+                        .ate_counts
+                        .iter()
+                        .map(|(_, x)| (*x, ATE_COUNT_WIDTH + 4 + digit_width * 3)),
+                );
+                spans.extend(layout.score_x.map(|x| (x, digit_width * SCORE_DIGITS)));
+                spans.sort();
+                for pair in spans.windows(2) {
+                    assert!(pair[0].0 + pair[0].1 <= pair[1].0, "{}: {:?}", width, spans);
+                }
+                let (x, w) = spans[spans.len() - 1];
+                assert!(
+                    spans[0].0 >= 10 && x + w <= 10 + width,
+                    "{}: {:?}",
+                    width,
+                    spans
+                );
+                if layout.speed_x.is_some() {
+                    assert!(layout.energy.w >= MIN_ENERGY_WIDTH);
+                }
+            }
+        }
+        // 広ければ全部並ぶ
+        let layout = InfoLayout::fit(0, 420, digit_width, MAX_ENERGY_WIDTH, true);
+        assert!(layout.speed_x.is_some() && layout.score_x.is_some());
+        assert_eq!(layout.ate_counts.len(), FoodColor::all().len());
+    }
+
+    #[test]
+    fn hud_fits_the_smallest_board() {
+        for cell_size in [1, 20] {
+            for players in [1, PLAYERS_MAX] {
+                let config = GameConfig {
+                    players,
+                    ..GameConfig::new(BOARD_MIN_LEN, BOARD_MIN_LEN, cell_size).unwrap()
+                };
+                let mut game = Game::with_config(config, 0);
+                game.players[0].score = 123456;
+                let fb = draw(&game, Overlay::None);
+                assert_eq!(fb.width, BOARD_MIN_LEN * cell_size);
+            }
+        }
+
+        // 100ピクセルなら満腹度・スピード・得点が重ならずに入る
+        let config = GameConfig::new(BOARD_MIN_LEN, BOARD_MIN_LEN, 20).unwrap();
+        let mut game = Game::with_config(config, 0);
+        game.players[0].energy = ENERGY_MAX;
+        game.players[0].score = 123456;
+        let fb = draw(&game, Overlay::None);
+        let digit_width = fb.digit_size().0;
+        let layout = InfoLayout::fit(0, fb.width, digit_width, MAX_ENERGY_WIDTH, true);
+        let (speed_x, score_x) = (layout.speed_x.unwrap(), layout.score_x.unwrap());
+        assert!(layout.ate_counts.is_empty());
+        assert_eq!(fb.pixel(layout.energy.w - 1, 10), ENERGY_COLORS[0]);
+        assert_eq!(fb.pixel(layout.energy.w, 10), INFO_BACKGROUND);
+        let drawn = |x0: i32, w: i32| (x0..x0 + w).any(|x| fb.pixel(x, 10) != INFO_BACKGROUND);
+        assert!(layout.energy.w < speed_x && drawn(speed_x, digit_width * 2));
+        assert!(!drawn(
+            speed_x + digit_width * 2,
+            score_x - speed_x - digit_width * 2
+        ));
+        assert_eq!(score_x + digit_width * SCORE_DIGITS, fb.width);
+        assert!(drawn(score_x, digit_width));
+    }
+
+    #[test]
+    fn score_is_right_aligned() {
+        let mut game = hud_game();
+        game.players[0].score = 5;
+        let fb = draw(&game, Overlay::None);
+        let digit_width = fb.digit_size().0;
+        let width = fb.width;
+        // 1桁なら右端の1文字分だけ描かれる
+        let drawn = |x0: i32| (x0..x0 + digit_width).any(|x| fb.pixel(x, 10) != INFO_BACKGROUND);
+        assert!(drawn(width - digit_width));
+        assert!(!drawn(width - digit_width * 2));
+    }
+
+    #[test]
+    fn versus_hud_is_split() {
+        let game = versus_game();
+        let fb = draw(&game, Overlay::None);
+        let half = game.config.screen_width() / 2;
+        assert_eq!(fb.pixel(0, 10), ENERGY_COLORS[0]);
+        assert_eq!(fb.pixel(half, 10), ENERGY_COLORS[1]);
+        assert_eq!(fb.pixel(half + PLAYER_MARK_WIDTH, 10), ENERGY_COLORS[1]);
+        assert_eq!(fb.pixel(half - 1, 10), INFO_BACKGROUND);
+    }
+
+    #[test]
+    fn centered_text_is_centered() {
+        let mut fb = Framebuffer::new(200, 20);
+        let style = TextStyle {
+            align: Align::Center,
+            ..TextStyle::new(WHITE)
+        };
+        let width = render_text(&mut fb, 100, 0, &style, "SNAKE").unwrap();
+        let columns: Vec<i32> = (0..200)
+            .filter(|x| (0..20).any(|y| fb.pixel(*x, y) == WHITE))
+            .collect();
+        let (left, right) = (columns[0], columns[columns.len() - 1]);
+        assert!(right - left < width);
+        assert!((left + right - 200).abs() <= 2);
+    }
+
+    // phaseが半分のところで右へ動いている途中のヘビ
+    fn moving_game(head: Point, bodies: Vec<Point>, last_positions: Vec<Point>) -> Game {
+        let mut game = Game::with_seed(0);
+        game.foods.clear();
+        let player = &mut game.players[0];
+        player.p = head;
+        player.direction = Direction::Right;
+        player.bodies = bodies;
+        player.last_positions = last_positions;
+        player.last_move_frame = 0;
+        player.next_move_frame = 8;
+        game.frame = 4;
+        game
+    }
+
+    fn draw_smooth(game: &Game) -> Framebuffer {
+        draw_smooth_at(game, 1.0)
+    }
+
+    fn draw_smooth_at(game: &Game, alpha: f32) -> Framebuffer {
+        let config = &game.config;
+        let mut fb = Framebuffer::new(config.screen_width(), config.screen_height());
+        let scene = Scene {
+            game,
+            overlay: Overlay::None,
+            motion: Motion::Smooth,
+            alpha,
+        };
+        render_scene(&mut fb, &scene).unwrap();
+        fb
+    }
+
+    fn draw_smooth_sprites(game: &Game, sheet: Bitmap) -> Framebuffer {
+        let config = &game.config;
+        let mut fb = Framebuffer::new(config.screen_width(), config.screen_height());
+        fb.set_sprites(sheet).unwrap();
+        let scene = Scene {
+            game,
+            overlay: Overlay::None,
+            motion: Motion::Smooth,
+            alpha: 1.0,
+        };
+        render_scene(&mut fb, &scene).unwrap();
+        fb
+    }
+
+    // 右へ進んできて、頭が(10, 10)から上へ曲がったところ
+    fn turning_game() -> Game {
+        let mut game = moving_game(
+            Point::new(10, 9),
+            vec![Point::new(10, 10), Point::new(9, 10), Point::new(8, 10)],
+            vec![
+                Point::new(10, 10),
+                Point::new(9, 10),
+                Point::new(8, 10),
+                Point::new(7, 10),
+            ],
+        );
+        game.players[0].direction = Direction::Up;
+        game
+    }
+
+    #[test]
+    fn smooth_motion_slides_the_tail_sprite() {
+        let game = moving_game(
+            Point::new(10, 10),
+            vec![Point::new(9, 10), Point::new(8, 10)],
+            vec![Point::new(9, 10), Point::new(8, 10), Point::new(7, 10)],
+        );
+        let fb = draw_smooth_sprites(&game, sprite_sheet());
+        let at = |x: i32, dx: i32| {
+            let cell = cell_rect(&game.config, x, 10);
+            fb.pixel(cell.x + dx, cell.y + 10)
+        };
+        let tint = PLAYER_COLORS[0];
+        // 頭は(9, 10)の右半分から、体は(8, 10)と(9, 10)の真ん中、尻尾は(7, 10)と(8, 10)の真ん中
+        assert_eq!(at(9, 5), tinted(Sprite::Straight, tint));
+        assert_eq!(at(8, 15), tinted(Sprite::Straight, tint));
+        assert_eq!(at(8, 5), tinted(Sprite::Tail, tint));
+        assert_eq!(at(7, 15), tinted(Sprite::Tail, tint));
+        assert_eq!(at(7, 5), BACKGROUND);
+    }
+
+    #[test]
+    fn smooth_motion_slides_every_sprite() {
+        let game = turning_game();
+        let fb = draw_smooth_sprites(&game, sprite_sheet());
+        let at = |x: i32, dx: i32, dy: i32| {
+            let cell = cell_rect(&game.config, x, 10);
+            fb.pixel(cell.x + dx, cell.y + dy)
+        };
+        let tint = PLAYER_COLORS[0];
+        // 曲がり角のマスにも、左から滑ってくる体が半分入っている
+        assert_eq!(at(10, 5, 15), tinted(Sprite::Straight, tint));
+        // 体がまだ届いていない所は曲がり角の絵で埋める
+        assert_eq!(at(10, 15, 15), tinted(Sprite::Corner, tint));
+        assert_eq!(at(9, 5, 15), tinted(Sprite::Straight, tint));
+        assert_eq!(at(7, 15, 15), tinted(Sprite::Tail, tint));
+        assert_eq!(at(7, 5, 15), BACKGROUND);
+    }
+
+    #[test]
+    fn smooth_sprites_match_golden() {
+        let bytes = include_bytes!("../resources/image/sprites.bmp");
+        let game = turning_game();
+        let fb = draw_smooth_sprites(&game, Bitmap::parse_bmp(bytes).unwrap());
+        let from = cell_rect(&game.config, 6, 8);
+        let to = cell_rect(&game.config, 11, 11);
+        assert_golden("smooth_sprites.ppm", &fb.crop(from.union(to)));
+    }
+
+    #[test]
+    fn smooth_motion_draws_between_cells() {
+        let game = moving_game(
+            Point::new(10, 10),
+            vec![Point::new(9, 10), Point::new(8, 10)],
+            vec![Point::new(9, 10), Point::new(8, 10), Point::new(7, 10)],
+        );
+        let fb = draw_smooth(&game);
+        let y = cell_rect(&game.config, 0, 10).y + 10;
+        // 頭は(9, 10)と(10, 10)の真ん中、尻尾は(7, 10)と(8, 10)の真ん中まで
+        assert_eq!(fb.pixel(195, y), PLAYER_COLORS[0]);
+        assert_eq!(fb.pixel(215, y), BACKGROUND);
+        assert_eq!(fb.pixel(152, y), PLAYER_COLORS[0]);
+        assert_eq!(fb.pixel(145, y), BACKGROUND);
+
+        // フレームの途中はその分だけ手前
+        assert_eq!(fb.pixel(209, y), PLAYER_COLORS[0]);
+        let fb = draw_smooth_at(&game, 0.0);
+        assert_eq!(fb.pixel(209, y), BACKGROUND);
+        assert_eq!(fb.pixel(207, y), PLAYER_COLORS[0]);
+
+        // 移動し終わったらマスの上
+        let fb = draw(&game, Overlay::None);
+        assert_eq!(fb.pixel(215, y), PLAYER_COLORS[0]);
+        assert_eq!(fb.pixel(165, y), PLAYER_COLORS[0]);
+        assert_eq!(fb.pixel(152, y), BACKGROUND);
+    }
+
+    #[test]
+    fn smooth_motion_splits_at_the_wrap_seam() {
+        let game = moving_game(
+            Point::new(0, 10),
+            vec![Point::new(20, 10)],
+            vec![Point::new(20, 10), Point::new(19, 10)],
+        );
+        let fb = draw_smooth(&game);
+        let y = cell_rect(&game.config, 0, 10).y + 10;
+        // 頭は右端と左端に半分ずつ
+        assert_eq!(fb.pixel(415, y), PLAYER_COLORS[0]);
+        assert_eq!(fb.pixel(5, y), PLAYER_COLORS[0]);
+        assert_eq!(fb.pixel(392, y), PLAYER_COLORS[0]);
+        // 盤面を横切る線は引かない
+        assert_eq!(fb.pixel(15, y), BACKGROUND);
+        assert_eq!(fb.pixel(200, y), BACKGROUND);
+        assert_eq!(fb.pixel(385, y), BACKGROUND);
+    }
+
+    #[test]
+    fn wrapped_rects_stay_on_the_board() {
+        let board = Rect::new(0, 20, 100, 100);
+        let inside = Rect::new(10, 30, 20, 20);
+        assert_eq!(wrapped_rects(inside, board), vec![inside]);
+        let corner = Rect::new(90, 110, 20, 20);
+        assert_eq!(
+            wrapped_rects(corner, board),
+            vec![
+                corner,
+                Rect::new(-10, 110, 20, 20),
+                Rect::new(90, 10, 20, 20),
+                Rect::new(-10, 10, 20, 20),
+            ]
+        );
+    }
+
+    #[test]
+    fn hud_matches_golden() {
+        assert_golden("hud.ppm", &hud(&draw(&hud_game(), Overlay::None)));
+        assert_golden("hud_versus.ppm", &hud(&draw(&versus_game(), Overlay::None)));
+    }
+}
@@ -0,0 +1,356 @@
+use crate::bot::BotLevel;
+use crate::level::Level;
+use crate::model::{Boundary, Command, Difficulty, Game, GameConfig, BOTS_MAX, PLAYERS_MAX};
+use std::fs;
+
+const HEADER: &str = "rust-snake replay 2";
+
+// seedと盤面の設定とフレームごとのコマンドがあればゲームを完全に再現できる
+pub struct Replay {
+    pub seed: u64,
+    pub config: GameConfig,
+    // プレイヤーごとのコマンド。対戦なら2つ並ぶ
+    pub commands: Vec<(i32, Vec<Command>)>,
+}
+
+impl Replay {
+    pub fn new(game: &Game) -> Self {
+        Replay {
+            seed: game.seed,
+            config: game.config.clone(),
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, frame: i32, commands: &[Command]) {
+        if commands.iter().any(|x| *x != Command::None) {
+            self.commands.push((frame, commands.to_vec()));
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|e| format!("cannot write replay {}: {}", path, e))
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "{}\nseed {}\nboard {} {}\nboundary {}\ndifficulty {}\n",
+            HEADER,
+            self.seed,
+            self.config.width,
+            self.config.height,
+            self.config.boundary.name(),
+            self.config.difficulty.name()
+        );
+        if let Some(level) = &self.config.level {
+            text += &format!("level {}\n", level.name);
+        }
+        if self.config.is_versus() {
+            text += &format!("players {}\n", self.config.players);
+        }
+        // コンピューターの操作は盤面から決まるので記録しない
+        if self.config.bots > 0 {
+            text += &format!(
+                "bots {}\nbot_level {}\n",
+                self.config.bots,
+                self.config.bot_level.name()
+            );
+        }
+        for (frame, commands) in &self.commands {
+            let commands: Vec<&str> = commands.iter().map(|x| command_to_str(*x)).collect();
+            text += &format!("{} {}\n", frame, commands.join(" "));
+        }
+        text
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("cannot read replay {}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // 数字で始まる行はフレームとプレイヤーごとのコマンド、それ以外は「名前 値」形式の設定
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate();
+
+        match lines.next() {
+            Some((_, line)) if line == HEADER => {}
+            _ => return Err("not a replay file".to_string()),
+        }
+
+        let mut seed = None;
+        let mut config = GameConfig::default();
+        // boardとlevelの両方があるときは大きさが合っていないといけない
+        let mut has_board = false;
+        let mut commands = Vec::new();
+        let mut last_frame = -1;
+        for (i, line) in lines {
+            if line.is_empty() {
+                continue;
+            }
+            let (name, value) = line.split_once(' ').unwrap_or((line, ""));
+            let invalid = || format!("line {}: invalid entry {:?}", i + 1, line);
+            if let Ok(frame) = name.parse::<i32>() {
+                let frame_commands = value
+                    .split(' ')
+                    .map(|x| str_to_command(x).ok_or_else(invalid))
+                    .collect::<Result<Vec<_>, _>>()?;
+                // 同じフレームに何行もあるのは、そのフレームの間に続けて押された操作
+                if frame < last_frame {
+                    return Err(format!("line {}: frames must not decrease", i + 1));
+                }
+                last_frame = frame;
+                commands.push((frame, frame_commands));
+                continue;
+            }
+            match name {
+                "seed" => seed = Some(value.parse::<u64>().map_err(|_| invalid())?),
+                "board" => {
+                    let (w, h) = value
+                        .split_once(' ')
+                        .and_then(|(w, h)| Some((w.parse::<i32>().ok()?, h.parse::<i32>().ok()?)))
+                        .ok_or_else(invalid)?;
+                    let board = GameConfig::new(w, h, config.cell_size)
+                        .map_err(|e| format!("line {}: {}", i + 1, e))?;
+                    if let Some(level) = &config.level {
+                        if (level.width, level.height) != (board.width, board.height) {
+                            return Err(format!(
+                                "line {}: board size differs from the level",
+                                i + 1
+                            ));
+                        }
+                    }
+                    config.width = board.width;
+                    config.height = board.height;
+                    has_board = true;
+                }
+                "boundary" => config.boundary = Boundary::from_name(value).ok_or_else(invalid)?,
+                "difficulty" => {
+                    config.difficulty = Difficulty::from_name(value).ok_or_else(invalid)?
+                }
+                "level" => {
+                    let level = Level::load(value)?;
+                    if has_board && (level.width, level.height) != (config.width, config.height) {
+                        return Err(format!("line {}: board size differs from the level", i + 1));
+                    }
+                    config.width = level.width;
+                    config.height = level.height;
+                    config.level = Some(level);
+                }
+                "players" => {
+                    config.players = value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|x| (1..=PLAYERS_MAX).contains(x))
+                        .ok_or_else(invalid)?
+                }
+                "bots" => {
+                    config.bots = value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|x| *x <= BOTS_MAX)
+                        .ok_or_else(invalid)?
+                }
+                "bot_level" => config.bot_level = BotLevel::from_name(value).ok_or_else(invalid)?,
+                _ => return Err(invalid()),
+            }
+        }
+
+        if commands.iter().any(|(_, x)| x.len() > config.players) {
+            return Err("more commands than players".to_string());
+        }
+        Ok(Replay {
+            seed: seed.ok_or_else(|| "seed is missing".to_string())?,
+            config,
+            commands,
+        })
+    }
+}
+
+// 記録されたコマンドをフレーム順に取り出す
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    cursor: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayer { replay, cursor: 0 }
+    }
+
+    // frameに記録された操作を押された順に返す。Game::update_roundsに渡す
+    // コマンドのないフレームでは空
+    pub fn rounds_at(&mut self, frame: i32) -> Vec<Vec<Command>> {
+        let mut rounds = Vec::new();
+        while let Some((f, commands)) = self.replay.commands.get(self.cursor) {
+            if *f != frame {
+                break;
+            }
+            rounds.push(commands.clone());
+            self.cursor += 1;
+        }
+        rounds
+    }
+
+    pub fn rewind(&mut self) {
+        self.cursor = 0;
+    }
+}
+
+fn command_to_str(command: Command) -> &'static str {
+    match command {
+        Command::None => "None",
+        Command::Left => "Left",
+        Command::Right => "Right",
+        Command::Down => "Down",
+        Command::Up => "Up",
+    }
+}
+
+fn str_to_command(s: &str) -> Option<Command> {
+    match s {
+        "None" => Some(Command::None),
+        "Left" => Some(Command::Left),
+        "Right" => Some(Command::Right),
+        "Down" => Some(Command::Down),
+        "Up" => Some(Command::Up),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_reproduces_recorded_game() {
+        let config = GameConfig {
+            boundary: Boundary::Walls,
+            difficulty: Difficulty::Hard,
+            ..GameConfig::new(12, 10, 20).unwrap()
+        };
+        let mut game = Game::with_config(config.clone(), 3);
+        let mut recording = Replay::new(&game);
+        let commands = [Command::Left, Command::Up, Command::Right, Command::Down];
+        for i in 0..2000 {
+            if game.is_over {
+                break;
+            }
+            let command = if i % 37 == 0 {
+                commands[i / 37 % commands.len()]
+            } else {
+                Command::None
+            };
+            recording.record(game.frame, &[command]);
+            game.update(command);
+        }
+
+        let replay = Replay::parse(&recording.to_text()).unwrap();
+        assert_eq!(replay.config, config);
+        let mut replayed = Game::with_config(replay.config.clone(), replay.seed);
+        let mut player = ReplayPlayer::new(replay);
+        for _ in 0..2000 {
+            let rounds = player.rounds_at(replayed.frame);
+            replayed.update_rounds(&rounds);
+        }
+
+        assert_eq!(replayed.players[0].p, game.players[0].p);
+        assert_eq!(replayed.players[0].bodies, game.players[0].bodies);
+        assert_eq!(replayed.is_over, game.is_over);
+        assert_eq!(replayed.frame, game.frame);
+    }
+
+    #[test]
+    fn versus_commands_are_recorded_per_player() {
+        let config = GameConfig {
+            players: 2,
+            ..GameConfig::default()
+        };
+        let game = Game::with_config(config.clone(), 1);
+        let mut recording = Replay::new(&game);
+        recording.record(0, &[Command::None, Command::None]);
+        recording.record(3, &[Command::Left, Command::None]);
+        recording.record(5, &[Command::None, Command::Right]);
+
+        let replay = Replay::parse(&recording.to_text()).unwrap();
+        assert_eq!(replay.config, config);
+        let mut player = ReplayPlayer::new(replay);
+        assert!(player.rounds_at(0).is_empty());
+        assert_eq!(
+            player.rounds_at(3),
+            vec![vec![Command::Left, Command::None]]
+        );
+        assert_eq!(
+            player.rounds_at(5),
+            vec![vec![Command::None, Command::Right]]
+        );
+    }
+
+    #[test]
+    fn games_with_bots_replay_identically() {
+        let config = GameConfig {
+            bots: 2,
+            bot_level: BotLevel::Hard,
+            ..GameConfig::default()
+        };
+        let mut game = Game::with_config(config.clone(), 9);
+        let recording = Replay::new(&game);
+        let replay = Replay::parse(&recording.to_text()).unwrap();
+        assert_eq!(replay.config, config);
+        let mut replayed = Game::with_config(replay.config.clone(), replay.seed);
+        for _ in 0..1000 {
+            game.update(Command::None);
+            replayed.update(Command::None);
+        }
+        for (a, b) in game.players.iter().zip(&replayed.players) {
+            assert_eq!(a.p, b.p);
+            assert_eq!(a.bodies, b.bodies);
+        }
+    }
+
+    #[test]
+    fn level_is_saved_by_name() {
+        let config = GameConfig::with_level(Level::load("box").unwrap(), 20).unwrap();
+        let game = Game::with_config(config.clone(), 1);
+        let replay = Replay::parse(&Replay::new(&game).to_text()).unwrap();
+        assert_eq!(replay.config, config);
+    }
+
+    #[test]
+    fn turns_within_one_frame_are_replayed_in_order() {
+        let mut game = Game::with_seed(0);
+        game.players[0].grow(&game.config);
+        let mut recording = Replay::new(&game);
+        let rounds = vec![vec![Command::Left], vec![Command::Down]];
+        for commands in &rounds {
+            recording.record(game.frame, commands);
+        }
+        game.update_rounds(&rounds);
+
+        let replay = Replay::parse(&recording.to_text()).unwrap();
+        let mut player = ReplayPlayer::new(replay);
+        assert_eq!(player.rounds_at(0), rounds);
+        assert!(player.rounds_at(1).is_empty());
+    }
+
+    #[test]
+    fn parse_rejects_broken_files() {
+        assert!(Replay::parse("hello").is_err());
+        assert!(Replay::parse(&format!("{}\nboard 21 20\n", HEADER)).is_err());
+        assert!(Replay::parse(&format!("{}\nseed 1\nboundary none\n", HEADER)).is_err());
+        assert!(Replay::parse(&format!("{}\nseed 1\ndifficulty extreme\n", HEADER)).is_err());
+        assert!(
+            Replay::parse(&format!("{}\nseed 1\nboard 21 20\n5 Up\n3 Left\n", HEADER)).is_err()
+        );
+        assert!(Replay::parse(&format!("{}\nseed 1\nboard 21 20\n5 Jump\n", HEADER)).is_err());
+        assert!(Replay::parse(&format!("{}\nseed 1\nplayers 3\n", HEADER)).is_err());
+        assert!(Replay::parse(&format!("{}\nseed 1\nbots 9\n", HEADER)).is_err());
+        assert!(Replay::parse(&format!("{}\nseed 1\nbot_level godlike\n", HEADER)).is_err());
+        assert!(Replay::parse(&format!("{}\nseed 1\n5 Up Down\n", HEADER)).is_err());
+        assert!(Replay::parse(&format!("{}\nseed 1\nboard 0 0\n", HEADER)).is_err());
+        assert!(Replay::parse(&format!("{}\nseed 1\nboard -3 5\n", HEADER)).is_err());
+        assert!(Replay::parse(&format!("{}\nseed 1\nlevel box\nboard 30 30\n", HEADER)).is_err());
+        assert!(Replay::parse(&format!("{}\nseed 1\nboard 30 30\nlevel box\n", HEADER)).is_err());
+        assert!(Replay::parse(&format!("{}\nseed 1\nboard 21 20\nlevel box\n", HEADER)).is_ok());
+    }
+}
@@ -0,0 +1,157 @@
+use crate::model::FoodColor;
+
+// 得点の決め方
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScoreRules {
+    // 前に食べてからこのフレーム数以内に食べるとコンボが続く
+    pub combo_window: i32,
+    // コンボ1段ごとに食べ物の得点に上乗せする割合 (%)
+    pub combo_step_percent: i32,
+    pub combo_max: i32,
+    // 体1つあたりの得点
+    pub length_bonus: i32,
+    // 満腹度がこれ未満のときに食べるとボーナス
+    pub hungry_threshold: i32,
+    pub hungry_bonus: i32,
+}
+
+impl Default for ScoreRules {
+    fn default() -> Self {
+        ScoreRules {
+            combo_window: 90,
+            combo_step_percent: 50,
+            combo_max: 4,
+            length_bonus: 5,
+            hungry_threshold: 20,
+            hungry_bonus: 50,
+        }
+    }
+}
+
+// 得点の内訳
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScoreBreakdown {
+    pub food: i32,
+    pub combo: i32,
+    pub length: i32,
+    pub hungry: i32,
+}
+
+impl ScoreBreakdown {
+    pub fn total(&self) -> i32 {
+        self.food + self.combo + self.length + self.hungry
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Scoring {
+    pub rules: ScoreRules,
+    pub breakdown: ScoreBreakdown,
+    // 今のコンボの段数。0ならコンボなし
+    pub combo: i32,
+    pub last_eat_frame: Option<i32>,
+}
+
+impl Scoring {
+    pub fn new(rules: ScoreRules) -> Self {
+        Scoring {
+            rules,
+            ..Default::default()
+        }
+    }
+
+    // 食べたときに呼ぶ。energyは食べる前の満腹度
+    pub fn on_eat(&mut self, color: &FoodColor, frame: i32, energy: i32) {
+        if *color == FoodColor::White {
+            // 白は得点なしでコンボも切れる
+            self.combo = 0;
+            self.last_eat_frame = None;
+            return;
+        }
+
+        self.combo = match self.last_eat_frame {
+            Some(last) if frame - last <= self.rules.combo_window => {
+                (self.combo + 1).min(self.rules.combo_max)
+            }
+            _ => 0,
+        };
+        self.last_eat_frame = Some(frame);
+
+        let points = color.points();
+        self.breakdown.food += points;
+        self.breakdown.combo += points * self.combo * self.rules.combo_step_percent / 100;
+        if energy < self.rules.hungry_threshold {
+            self.breakdown.hungry += self.rules.hungry_bonus;
+        }
+    }
+
+    // 長さのボーナスは一番長かったときの長さで決まる。白い食べ物で縮んでも得点は減らない
+    pub fn on_length_changed(&mut self, length: usize) {
+        let bonus = length as i32 * self.rules.length_bonus;
+        self.breakdown.length = self.breakdown.length.max(bonus);
+    }
+
+    pub fn total(&self) -> i32 {
+        self.breakdown.total()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quick_eating_builds_combo() {
+        let mut scoring = Scoring::default();
+        scoring.on_eat(&FoodColor::Blue, 0, 100);
+        scoring.on_eat(&FoodColor::Blue, 50, 100);
+        scoring.on_eat(&FoodColor::Blue, 100, 100);
+        assert_eq!(scoring.combo, 2);
+        assert_eq!(scoring.breakdown.food, 30);
+        assert_eq!(scoring.breakdown.combo, 5 + 10);
+    }
+
+    #[test]
+    fn combo_breaks_after_window_or_white() {
+        let mut scoring = Scoring::default();
+        scoring.on_eat(&FoodColor::Red, 0, 100);
+        scoring.on_eat(&FoodColor::Red, 10, 100);
+        assert_eq!(scoring.combo, 1);
+        scoring.on_eat(&FoodColor::Red, 500, 100);
+        assert_eq!(scoring.combo, 0);
+        scoring.on_eat(&FoodColor::Red, 510, 100);
+        scoring.on_eat(&FoodColor::White, 520, 100);
+        assert_eq!(scoring.combo, 0);
+        scoring.on_eat(&FoodColor::Red, 530, 100);
+        assert_eq!(scoring.combo, 0);
+    }
+
+    #[test]
+    fn combo_is_capped() {
+        let mut scoring = Scoring::default();
+        for i in 0..10 {
+            scoring.on_eat(&FoodColor::Yellow, i, 100);
+        }
+        assert_eq!(scoring.combo, scoring.rules.combo_max);
+    }
+
+    #[test]
+    fn eating_when_hungry_gives_bonus() {
+        let mut scoring = Scoring::default();
+        scoring.on_eat(&FoodColor::Blue, 0, 5);
+        assert_eq!(scoring.breakdown.hungry, scoring.rules.hungry_bonus);
+        scoring.on_eat(&FoodColor::Blue, 1000, 50);
+        assert_eq!(scoring.breakdown.hungry, scoring.rules.hungry_bonus);
+    }
+
+    #[test]
+    fn length_bonus_keeps_the_longest_length() {
+        let mut scoring = Scoring::default();
+        scoring.on_length_changed(4);
+        assert_eq!(scoring.breakdown.length, 20);
+        scoring.on_length_changed(2);
+        assert_eq!(scoring.total(), 20);
+        scoring.on_length_changed(6);
+        assert_eq!(scoring.total(), 30);
+    }
+}
@@ -0,0 +1,430 @@
+use crate::bot::{command_on_move, Autopilot, BotLevel, Strategy};
+use crate::model::{DeathCause, FoodColor, Game, GameConfig};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+// 1ゲームがこのフレーム数を超えたら打ち切る
+pub const FRAMES_MAX: i32 = 100_000;
+// 得点のヒストグラムの区間の数
+pub const HISTOGRAM_BINS: usize = 10;
+
+// シミュレーションで1人目のヘビを動かす考え方
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Driver {
+    Bot(BotLevel),
+    Autopilot,
+}
+
+impl Driver {
+    pub fn all() -> Vec<Self> {
+        let mut all: Vec<Self> = BotLevel::all().into_iter().map(Driver::Bot).collect();
+        all.push(Driver::Autopilot);
+        all
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Driver::Bot(level) => level.name(),
+            Driver::Autopilot => "autopilot",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().into_iter().find(|x| x.name() == name)
+    }
+
+    pub fn strategy(&self) -> Box<dyn Strategy> {
+        match self {
+            Driver::Bot(level) => level.strategy(),
+            Driver::Autopilot => Box::new(Autopilot),
+        }
+    }
+}
+
+// seedを変えながら同じ設定のゲームを何度も遊ぶ
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    // 対戦の設定でも1人で遊ぶ。コンピューターのヘビは出せる
+    pub config: GameConfig,
+    pub driver: Driver,
+    pub games: usize,
+    // first_seedから1つずつ増やす
+    pub first_seed: u64,
+    pub threads: usize,
+    pub frames_max: i32,
+}
+
+// 1ゲームの結果
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameResult {
+    pub seed: u64,
+    pub score: i32,
+    pub length: i32,
+    pub frames: i32,
+    // クリアしたときと打ち切ったときはNone
+    pub death: Option<DeathCause>,
+    pub is_cleared: bool,
+    pub ate_counts: HashMap<FoodColor, i32>,
+}
+
+impl Simulation {
+    pub fn new(config: GameConfig, driver: Driver) -> Self {
+        Simulation {
+            config: GameConfig {
+                players: 1,
+                ..config
+            },
+            driver,
+            games: 1000,
+            first_seed: 0,
+            threads: thread::available_parallelism().map_or(1, |x| x.get()),
+            frames_max: FRAMES_MAX,
+        }
+    }
+
+    pub fn play(&self, seed: u64) -> GameResult {
+        let mut game = Game::with_config(self.config.clone(), seed);
+        let mut strategy = self.driver.strategy();
+        while !game.is_over && game.frame < self.frames_max {
+            let command = command_on_move(strategy.as_mut(), &game, 0);
+            game.update(command);
+            game.take_sounds();
+        }
+        let player = &game.players[0];
+        GameResult {
+            seed,
+            score: player.score,
+            length: player.bodies.len() as i32 + 1,
+            frames: game.frame,
+            death: player.death,
+            is_cleared: game.is_cleared,
+            ate_counts: player.ate_counts.clone(),
+        }
+    }
+
+    // 空いたスレッドから次のゲームを取っていく。結果はseedの順で、スレッド数によらない
+    pub fn run(&self) -> Vec<GameResult> {
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::with_capacity(self.games));
+        thread::scope(|scope| {
+            for _ in 0..self.threads.clamp(1, self.games.max(1)) {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= self.games {
+                        break;
+                    }
+                    let result = self.play(self.first_seed + i as u64);
+                    results.lock().unwrap().push(result);
+                });
+            }
+        });
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|x| x.seed);
+        results
+    }
+}
+
+// 得点の分布
+#[derive(Clone, Debug, PartialEq)]
+pub struct Distribution {
+    pub min: i32,
+    pub p10: i32,
+    pub p25: i32,
+    pub median: i32,
+    pub p75: i32,
+    pub p90: i32,
+    pub max: i32,
+    pub mean: f64,
+    // (区間の下限, 件数)。区間の幅はどれも同じ
+    pub histogram: Vec<(i32, usize)>,
+}
+
+impl Distribution {
+    pub fn new(values: &[i32]) -> Self {
+        let mut sorted = values.to_vec();
+        sorted.sort();
+        // 最も近い順位の値
+        let percentile = |p: usize| match sorted.len() {
+            0 => 0,
+            n => sorted[(n * p).div_ceil(100).clamp(1, n) - 1],
+        };
+        let min = sorted.first().copied().unwrap_or(0);
+        let max = sorted.last().copied().unwrap_or(0);
+        let width = ((max - min) / HISTOGRAM_BINS as i32 + 1).max(1);
+        let mut histogram: Vec<(i32, usize)> = (0..HISTOGRAM_BINS as i32)
+            .map(|i| (min + width * i, 0))
+            .collect();
+        for x in &sorted {
+            histogram[((x - min) / width) as usize].1 += 1;
+        }
+        Distribution {
+            min,
+            p10: percentile(10),
+            p25: percentile(25),
+            median: percentile(50),
+            p75: percentile(75),
+            p90: percentile(90),
+            max,
+            mean: mean(sorted.iter().map(|x| *x as f64), sorted.len()),
+            histogram,
+        }
+    }
+}
+
+// シミュレーション全体の集計
+#[derive(Clone, Debug, PartialEq)]
+pub struct Summary {
+    pub driver: Driver,
+    pub games: usize,
+    pub first_seed: u64,
+    pub scores: Distribution,
+    pub mean_length: f64,
+    pub mean_frames: f64,
+    // DeathCause::all()の順
+    pub deaths: Vec<(DeathCause, usize)>,
+    pub cleared: usize,
+    // frames_maxで打ち切ったゲーム
+    pub timeouts: usize,
+    // 色ごとに食べた数の合計。FoodColor::all()の順
+    pub foods: Vec<(FoodColor, i64)>,
+}
+
+impl Summary {
+    pub fn new(simulation: &Simulation, results: &[GameResult]) -> Self {
+        let n = results.len();
+        let scores: Vec<i32> = results.iter().map(|x| x.score).collect();
+        Summary {
+            driver: simulation.driver,
+            games: n,
+            first_seed: simulation.first_seed,
+            scores: Distribution::new(&scores),
+            mean_length: mean(results.iter().map(|x| x.length as f64), n),
+            mean_frames: mean(results.iter().map(|x| x.frames as f64), n),
+            deaths: DeathCause::all()
+                .into_iter()
+                .map(|cause| {
+                    let count = results.iter().filter(|x| x.death == Some(cause)).count();
+                    (cause, count)
+                })
+                .collect(),
+            cleared: results.iter().filter(|x| x.is_cleared).count(),
+            timeouts: results
+                .iter()
+                .filter(|x| x.death.is_none() && !x.is_cleared)
+                .count(),
+            foods: FoodColor::all()
+                .into_iter()
+                .map(|color| {
+                    let total = results
+                        .iter()
+                        .map(|x| *x.ate_counts.get(&color).unwrap_or(&0) as i64)
+                        .sum();
+                    (color, total)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let scores = &self.scores;
+        let mut text = format!("strategy   {}\n", self.driver.name());
+        text += &format!(
+            "games      {} (seeds {}-{})\n",
+            self.games,
+            self.first_seed,
+            self.first_seed + self.games.max(1) as u64 - 1
+        );
+        text += &format!(
+            "score      mean {:.1}  min {}  p10 {}  p25 {}  median {}  p75 {}  p90 {}  max {}\n",
+            scores.mean,
+            scores.min,
+            scores.p10,
+            scores.p25,
+            scores.median,
+            scores.p75,
+            scores.p90,
+            scores.max
+        );
+        text += &format!("length     mean {:.1}\n", self.mean_length);
+        text += &format!("frames     mean {:.1}\n", self.mean_frames);
+        let deaths: Vec<String> = self
+            .deaths
+            .iter()
+            .map(|(cause, count)| (cause.name(), *count))
+            .chain([("cleared", self.cleared), ("timeout", self.timeouts)])
+            .map(|(name, count)| {
+                let percent = per(count as f64 * 100.0, self.games);
+                format!("{} {} ({:.1}%)", name, count, percent)
+            })
+            .collect();
+        text += &format!("end        {}\n", deaths.join("  "));
+        let foods: Vec<String> = self
+            .foods
+            .iter()
+            .map(|(color, total)| format!("{} {:.2}", color.name(), per(*total as f64, self.games)))
+            .collect();
+        text += &format!("foods/game {}\n", foods.join("  "));
+
+        text += "histogram\n";
+        let width = match scores.histogram.as_slice() {
+            [(a, _), (b, _), ..] => b - a,
+            _ => 1,
+        };
+        let count_max = scores.histogram.iter().map(|x| x.1).max().unwrap_or(0);
+        for (low, count) in &scores.histogram {
+            // 一番多い区間を40文字にする
+            let bar = "#".repeat((count * 40).checked_div(count_max).unwrap_or(0));
+            let line = format!("  {:>7}-{:<7} {:>6} {}", low, low + width - 1, count, bar);
+            text += line.trim_end();
+            text += "\n";
+        }
+        text
+    }
+
+    pub fn to_json(&self) -> String {
+        let scores = &self.scores;
+        let histogram: Vec<String> = scores
+            .histogram
+            .iter()
+            .map(|(low, count)| format!("{{\"min\": {}, \"count\": {}}}", low, count))
+            .collect();
+        let deaths: Vec<String> = self
+            .deaths
+            .iter()
+            .map(|(cause, count)| format!("\"{}\": {}", cause.name(), count))
+            .collect();
+        let foods: Vec<String> = self
+            .foods
+            .iter()
+            .map(|(color, total)| format!("\"{}\": {}", color.name(), total))
+            .collect();
+        let mut json = "{\n".to_string();
+        json += &format!("  \"strategy\": \"{}\",\n", self.driver.name());
+        json += &format!("  \"games\": {},\n", self.games);
+        json += &format!("  \"first_seed\": {},\n", self.first_seed);
+        json += &format!(
+            "  \"score\": {{\"mean\": {:.2}, \"min\": {}, \"p10\": {}, \"p25\": {}, \"median\": {}, \"p75\": {}, \"p90\": {}, \"max\": {}, \"histogram\": [{}]}},\n",
+            scores.mean,
+            scores.min,
+            scores.p10,
+            scores.p25,
+            scores.median,
+            scores.p75,
+            scores.p90,
+            scores.max,
+            histogram.join(", ")
+        );
+        json += &format!("  \"mean_length\": {:.2},\n", self.mean_length);
+        json += &format!("  \"mean_frames\": {:.2},\n", self.mean_frames);
+        json += &format!("  \"deaths\": {{{}}},\n", deaths.join(", "));
+        json += &format!("  \"cleared\": {},\n", self.cleared);
+        json += &format!("  \"timeouts\": {},\n", self.timeouts);
+        json += &format!("  \"foods\": {{{}}}\n", foods.join(", "));
+        json += "}\n";
+        json
+    }
+}
+
+// n件あたりの値。0件なら0
+fn per(total: f64, n: usize) -> f64 {
+    match n {
+        0 => 0.0,
+        n => total / n as f64,
+    }
+}
+
+fn mean(values: impl Iterator<Item = f64>, n: usize) -> f64 {
+    per(values.sum(), n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_simulation(threads: usize) -> Simulation {
+        let config = GameConfig::new(8, 8, 20).unwrap();
+        Simulation {
+            games: 6,
+            first_seed: 10,
+            threads,
+            frames_max: 3000,
+            ..Simulation::new(config, Driver::Bot(BotLevel::Hard))
+        }
+    }
+
+    fn result(score: i32, death: Option<DeathCause>, red: i32) -> GameResult {
+        GameResult {
+            seed: 0,
+            score,
+            length: 3,
+            frames: 100,
+            death,
+            is_cleared: false,
+            ate_counts: HashMap::from([(FoodColor::Red, red)]),
+        }
+    }
+
+    #[test]
+    fn results_do_not_depend_on_threads() {
+        let one = small_simulation(1).run();
+        let many = small_simulation(4).run();
+        assert_eq!(one, many);
+        let seeds: Vec<u64> = one.iter().map(|x| x.seed).collect();
+        assert_eq!(seeds, (10..16).collect::<Vec<_>>());
+        assert!(one.iter().all(|x| x.death.is_some() || x.frames == 3000));
+    }
+
+    #[test]
+    fn distribution_uses_nearest_rank() {
+        let scores: Vec<i32> = (1..=20).map(|x| x * 10).collect();
+        let d = Distribution::new(&scores);
+        assert_eq!((d.min, d.max), (10, 200));
+        assert_eq!(
+            (d.p10, d.p25, d.median, d.p75, d.p90),
+            (20, 50, 100, 150, 180)
+        );
+        assert_eq!(d.mean, 105.0);
+        assert_eq!(d.histogram.len(), HISTOGRAM_BINS);
+        assert_eq!(d.histogram.iter().map(|x| x.1).sum::<usize>(), 20);
+        assert_eq!(Distribution::new(&[]).median, 0);
+    }
+
+    #[test]
+    fn summary_counts_deaths_and_foods() {
+        let results = [
+            result(100, Some(DeathCause::Starvation), 2),
+            result(300, Some(DeathCause::Poo), 5),
+            result(200, Some(DeathCause::Starvation), 0),
+            result(50, None, 1),
+        ];
+        let summary = Summary::new(&small_simulation(1), &results);
+        assert_eq!(summary.games, 4);
+        assert_eq!(summary.scores.median, 100);
+        assert_eq!(summary.mean_length, 3.0);
+        let deaths: HashMap<DeathCause, usize> = summary.deaths.iter().copied().collect();
+        assert_eq!(deaths[&DeathCause::Starvation], 2);
+        assert_eq!(deaths[&DeathCause::Poo], 1);
+        assert_eq!(deaths[&DeathCause::SelfBite], 0);
+        assert_eq!(summary.timeouts, 1);
+        assert!(summary.foods.contains(&(FoodColor::Red, 8)));
+        assert!(summary.foods.contains(&(FoodColor::White, 0)));
+
+        let text = summary.to_text();
+        assert!(text.contains("starvation 2 (50.0%)"), "{}", text);
+        assert!(text.contains("red 2.00"), "{}", text);
+        let json = summary.to_json();
+        assert!(json.contains("\"deaths\": {\"wall\": 0, \"self\": 0, \"poo\": 1, \"starvation\": 2, \"collision\": 0}"), "{}", json);
+        assert!(json.contains("\"strategy\": \"hard\""), "{}", json);
+        assert_eq!(json.matches('{').count(), json.matches('}').count());
+    }
+
+    #[test]
+    fn driver_names_round_trip() {
+        for driver in Driver::all() {
+            assert_eq!(Driver::from_name(driver.name()), Some(driver));
+        }
+        assert_eq!(Driver::from_name("random"), None);
+    }
+}
@@ -0,0 +1,198 @@
+use crate::model::{get_direction, Direction, FoodColor, Player};
+use crate::render::{Color, Rect};
+
+// resources/image/sprites.bmpの絵。横一列に正方形の絵がSprite::all()の順に並ぶ
+// この色の所は透明
+pub const COLOR_KEY: Color = Color::rgb(255, 0, 255);
+pub const SPRITES_PATH: &str = "resources/image/sprites.bmp";
+
+// 体の絵は白で描いてあり、ヘビの色を掛けて使う
+// 向きは上向きが基準で、描くときに回す
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Sprite {
+    // 上下につながる体
+    Straight,
+    // 上と右につながる体
+    Corner,
+    // 上だけにつながる尻尾
+    Tail,
+    Food(FoodColor),
+    Poo,
+}
+
+impl Sprite {
+    pub fn all() -> Vec<Self> {
+        let mut sprites = vec![Sprite::Straight, Sprite::Corner, Sprite::Tail];
+        sprites.extend(FoodColor::all().into_iter().map(Sprite::Food));
+        sprites.push(Sprite::Poo);
+        sprites
+    }
+
+    pub fn index(&self) -> usize {
+        Self::all().iter().position(|x| x == self).unwrap()
+    }
+}
+
+// 絵の大きさと並び
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpriteSheet {
+    pub tile_size: i32,
+}
+
+impl SpriteSheet {
+    // 画像の大きさが絵の数と合っているか確かめる
+    pub fn new(width: i32, height: i32) -> Result<Self, String> {
+        let count = Sprite::all().len() as i32;
+        if height <= 0 || width != height * count {
+            return Err(format!(
+                "invalid sprite sheet: {}x{}, expected {} square tiles in a row",
+                width, height, count
+            ));
+        }
+        Ok(SpriteSheet { tile_size: height })
+    }
+
+    // 画像の中の絵の位置
+    pub fn tile(&self, sprite: &Sprite) -> Rect {
+        let size = self.tile_size;
+        Rect::new(sprite.index() as i32 * size, 0, size, size)
+    }
+}
+
+// 体のi番目に使う絵と、上向きの絵をどの向きに回すか
+// 前の体と同じマスに重なっている体 (壁際で伸びた直後) は描かないのでNone
+pub fn body_sprite(player: &Player, i: usize) -> Option<(Sprite, Direction)> {
+    let p = &player.bodies[i];
+    let prev = if i == 0 {
+        &player.p
+    } else {
+        &player.bodies[i - 1]
+    };
+    let front = get_direction(p.clone(), prev.clone())?;
+    let back = player
+        .bodies
+        .get(i + 1)
+        .and_then(|next| get_direction(p.clone(), next.clone()));
+    Some(body_piece(front, back))
+}
+
+// 前 (頭の側) と後ろにつながる体の絵。後ろがなければ尻尾
+pub fn body_piece(front: Direction, back: Option<Direction>) -> (Sprite, Direction) {
+    let back = match back {
+        None => return (Sprite::Tail, front),
+        Some(back) if back == front.opposite() => return (Sprite::Straight, front),
+        Some(back) => back,
+    };
+    // 曲がり角の絵は上と右、つまり時計回りに隣り合う2方向をつなぐ
+    let rotation = if clockwise(front) == back {
+        front
+    } else {
+        back
+    };
+    (Sprite::Corner, rotation)
+}
+
+fn clockwise(direction: Direction) -> Direction {
+    match direction {
+        Direction::Up => Direction::Right,
+        Direction::Right => Direction::Down,
+        Direction::Down => Direction::Left,
+        Direction::Left => Direction::Up,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Point;
+
+    fn snake(head: (i32, i32), bodies: &[(i32, i32)]) -> Player {
+        let mut player = Player::at(Point::new(head.0, head.1), Direction::Up);
+        player.bodies = bodies.iter().map(|(x, y)| Point::new(*x, *y)).collect();
+        player
+    }
+
+    #[test]
+    fn sheet_size_must_match_the_sprites() {
+        let sheet = SpriteSheet::new(160, 20).unwrap();
+        assert_eq!(sheet.tile(&Sprite::Straight), Rect::new(0, 0, 20, 20));
+        assert_eq!(
+            sheet.tile(&Sprite::Food(FoodColor::Red)),
+            Rect::new(80, 0, 20, 20)
+        );
+        assert_eq!(sheet.tile(&Sprite::Poo), Rect::new(140, 0, 20, 20));
+        assert!(SpriteSheet::new(140, 20).is_err());
+        assert!(SpriteSheet::new(0, 0).is_err());
+    }
+
+    #[test]
+    fn straight_and_tail_face_the_head() {
+        let player = snake((5, 5), &[(5, 6), (5, 7)]);
+        assert_eq!(
+            body_sprite(&player, 0),
+            Some((Sprite::Straight, Direction::Up))
+        );
+        assert_eq!(body_sprite(&player, 1), Some((Sprite::Tail, Direction::Up)));
+        let player = snake((4, 5), &[(5, 5), (6, 5)]);
+        assert_eq!(
+            body_sprite(&player, 0),
+            Some((Sprite::Straight, Direction::Left))
+        );
+        assert_eq!(
+            body_sprite(&player, 1),
+            Some((Sprite::Tail, Direction::Left))
+        );
+    }
+
+    #[test]
+    fn corners_are_rotated_to_their_openings() {
+        // 上と右
+        let player = snake((5, 4), &[(5, 5), (6, 5)]);
+        assert_eq!(
+            body_sprite(&player, 0),
+            Some((Sprite::Corner, Direction::Up))
+        );
+        // 右と下
+        let player = snake((5, 6), &[(5, 5), (6, 5)]);
+        assert_eq!(
+            body_sprite(&player, 0),
+            Some((Sprite::Corner, Direction::Right))
+        );
+        // 下と左
+        let player = snake((4, 5), &[(5, 5), (5, 6)]);
+        assert_eq!(
+            body_sprite(&player, 0),
+            Some((Sprite::Corner, Direction::Down))
+        );
+        // 左と上
+        let player = snake((5, 4), &[(5, 5), (4, 5)]);
+        assert_eq!(
+            body_sprite(&player, 0),
+            Some((Sprite::Corner, Direction::Left))
+        );
+    }
+
+    #[test]
+    fn wrapped_bodies_connect_across_the_edge() {
+        let player = snake((0, 5), &[(20, 5), (19, 5)]);
+        assert_eq!(
+            body_sprite(&player, 0),
+            Some((Sprite::Straight, Direction::Right))
+        );
+        let player = snake((3, 19), &[(3, 0), (4, 0)]);
+        assert_eq!(
+            body_sprite(&player, 0),
+            Some((Sprite::Corner, Direction::Up))
+        );
+    }
+
+    #[test]
+    fn stacked_tail_is_not_drawn() {
+        let player = snake((0, 1), &[(0, 0), (0, 0)]);
+        assert_eq!(
+            body_sprite(&player, 0),
+            Some((Sprite::Tail, Direction::Down))
+        );
+        assert_eq!(body_sprite(&player, 1), None);
+    }
+}
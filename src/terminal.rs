@@ -0,0 +1,488 @@
+use crate::framebuffer::Framebuffer;
+use crate::model::{Boundary, FoodColor, Game, GameConfig, ENERGY_MAX};
+use crate::render::{
+    self, get_draw_color, Color, Motion, Renderer, BACKGROUND, ENERGY_COLORS, WHITE,
+};
+
+// 端末に描く文字の画面。盤面は上半分のブロック文字で1文字に縦2ピクセルを描く
+// 実際に端末へ出力するのはsnake-tui
+
+pub const UPPER_HALF: char = '▀';
+// 盤面の上の情報表示と、下のメッセージの行数
+pub const HUD_ROWS: i32 = 1;
+pub const STATUS_ROWS: i32 = 1;
+// 盤面を囲む枠の太さ (文字)
+pub const FRAME: i32 = 1;
+pub const ENERGY_BAR_WIDTH: i32 = 10;
+pub const BLACK: Color = Color::rgb(0, 0, 0);
+const HUD_BACKGROUND: Color = Color::rgb(64, 64, 64);
+const EMPTY_ENERGY: Color = Color::rgb(32, 32, 32);
+const WRAP_FRAME: Color = Color::rgb(128, 128, 128);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub fg: Color,
+    pub bg: Color,
+}
+
+impl Cell {
+    pub fn blank(bg: Color) -> Self {
+        Cell {
+            ch: ' ',
+            fg: WHITE,
+            bg,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Screen {
+    pub width: i32,
+    pub height: i32,
+    pub cells: Vec<Cell>,
+}
+
+// 前の画面から変わった文字の並び。xから右へcellsを書く
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub x: i32,
+    pub y: i32,
+    pub cells: Vec<Cell>,
+}
+
+impl Screen {
+    pub fn new(width: i32, height: i32) -> Self {
+        Screen {
+            width,
+            height,
+            cells: vec![Cell::blank(BLACK); (width.max(0) * height.max(0)) as usize],
+        }
+    }
+
+    pub fn cell(&self, x: i32, y: i32) -> Cell {
+        self.cells[(y * self.width + x) as usize]
+    }
+
+    // 画面の外は書かない
+    pub fn set(&mut self, x: i32, y: i32, cell: Cell) {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return;
+        }
+        self.cells[(y * self.width + x) as usize] = cell;
+    }
+
+    // 書き終わった次のxを返す
+    pub fn put_text(&mut self, x: i32, y: i32, text: &str, fg: Color, bg: Color) -> i32 {
+        let mut x = x;
+        for ch in text.chars() {
+            self.set(x, y, Cell { ch, fg, bg });
+            x += 1;
+        }
+        x
+    }
+
+    // 1行分の文字。テストで使う
+    pub fn line(&self, y: i32) -> String {
+        (0..self.width).map(|x| self.cell(x, y).ch).collect()
+    }
+
+    // prevから書き換える必要がある部分。大きさが違うときは全部
+    pub fn changes(&self, prev: Option<&Screen>) -> Vec<Span> {
+        let prev = prev.filter(|x| x.width == self.width && x.height == self.height);
+        let mut spans: Vec<Span> = Vec::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let cell = self.cell(x, y);
+                if prev.is_some_and(|prev| prev.cell(x, y) == cell) {
+                    continue;
+                }
+                match spans.last_mut() {
+                    Some(span) if span.y == y && span.x + span.cells.len() as i32 == x => {
+                        span.cells.push(cell)
+                    }
+                    _ => spans.push(Span {
+                        x,
+                        y,
+                        cells: vec![cell],
+                    }),
+                }
+            }
+        }
+        spans
+    }
+}
+
+// 端末の大きさに合わせた配置。盤面の1マスはscale文字×scale/2行になる
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+    pub scale: i32,
+    // 枠を含めた盤面の左上 (文字)
+    pub left: i32,
+    pub top: i32,
+    // 端末の大きさ
+    pub columns: i32,
+    pub rows: i32,
+}
+
+impl Layout {
+    // 入る中でいちばん大きく描く
+    pub fn fit(config: &GameConfig, columns: i32, rows: i32) -> Result<Self, String> {
+        let needed = |scale: i32| {
+            (
+                config.width * scale + FRAME * 2,
+                HUD_ROWS + (config.height * scale + 1) / 2 + FRAME * 2 + STATUS_ROWS,
+            )
+        };
+        let fits = |scale: i32| {
+            let (w, h) = needed(scale);
+            w <= columns && h <= rows
+        };
+        if !fits(1) {
+            let (w, h) = needed(1);
+            return Err(format!(
+                "terminal is too small: {}x{} needed, {}x{} available",
+                w, h, columns, rows
+            ));
+        }
+        let scale = (1..).take_while(|x| fits(*x)).last().unwrap_or(1);
+        Ok(Layout {
+            scale,
+            left: (columns - needed(scale).0) / 2,
+            top: HUD_ROWS,
+            columns,
+            rows,
+        })
+    }
+
+    // 枠を含めた盤面の大きさ (文字)
+    pub fn board_size(&self, config: &GameConfig) -> (i32, i32) {
+        (
+            config.width * self.scale + FRAME * 2,
+            (config.height * self.scale + 1) / 2 + FRAME * 2,
+        )
+    }
+}
+
+// 盤面はピクセルでFramebufferに描いてから文字にする
+pub struct TerminalView {
+    pub layout: Layout,
+    board: Framebuffer,
+}
+
+impl TerminalView {
+    pub fn new(config: &GameConfig, columns: i32, rows: i32) -> Result<Self, String> {
+        let layout = Layout::fit(config, columns, rows)?;
+        Ok(TerminalView {
+            layout,
+            board: Framebuffer::new(config.width * layout.scale, config.height * layout.scale),
+        })
+    }
+
+    // statusは盤面の下に中央揃えで出すメッセージ
+    pub fn draw(&mut self, game: &Game, status: &str) -> Result<Screen, String> {
+        let layout = self.layout;
+        let mut screen = Screen::new(layout.columns, layout.rows);
+        self.draw_board(&mut screen, game)?;
+        self.draw_frame(&mut screen, &game.config);
+        draw_hud(&mut screen, game, &layout);
+
+        let (width, height) = layout.board_size(&game.config);
+        let text_width = status.chars().count() as i32;
+        screen.put_text(
+            layout.left + (width - text_width) / 2,
+            layout.top + height,
+            status,
+            WHITE,
+            BLACK,
+        );
+        Ok(screen)
+    }
+
+    fn draw_board(&mut self, screen: &mut Screen, game: &Game) -> Result<(), String> {
+        let fb = &mut self.board;
+        fb.clear(BACKGROUND)?;
+        render::render_board(fb, game, self.layout.scale, 0, Motion::Grid, 1.0)?;
+
+        let left = self.layout.left + FRAME;
+        let top = self.layout.top + FRAME;
+        for y in 0..(fb.height + 1) / 2 {
+            for x in 0..fb.width {
+                let upper = fb.pixel(x, y * 2);
+                let lower = if y * 2 + 1 < fb.height {
+                    fb.pixel(x, y * 2 + 1)
+                } else {
+                    BLACK
+                };
+                let cell = if upper == lower {
+                    Cell::blank(upper)
+                } else {
+                    Cell {
+                        ch: UPPER_HALF,
+                        fg: upper,
+                        bg: lower,
+                    }
+                };
+                screen.set(left + x, top + y, cell);
+            }
+        }
+        Ok(())
+    }
+
+    // 壁に囲まれた盤面は壁の色で塗り、つながった盤面は細い線で囲む
+    fn draw_frame(&self, screen: &mut Screen, config: &GameConfig) {
+        let (width, height) = self.layout.board_size(config);
+        let (left, top) = (self.layout.left, self.layout.top);
+        let (right, bottom) = (left + width - 1, top + height - 1);
+        let is_walls = config.boundary == Boundary::Walls;
+        let frame = |ch: char| {
+            if is_walls {
+                Cell::blank(HUD_BACKGROUND)
+            } else {
+                Cell {
+                    ch,
+                    fg: WRAP_FRAME,
+                    bg: BLACK,
+                }
+            }
+        };
+        for x in left + 1..right {
+            screen.set(x, top, frame('─'));
+            screen.set(x, bottom, frame('─'));
+        }
+        for y in top + 1..bottom {
+            screen.set(left, y, frame('│'));
+            screen.set(right, y, frame('│'));
+        }
+        screen.set(left, top, frame('┌'));
+        screen.set(right, top, frame('┐'));
+        screen.set(left, bottom, frame('└'));
+        screen.set(right, bottom, frame('┘'));
+    }
+}
+
+// 盤面の上の1行。SDL版と同じく満腹度・スピードレベル・食べた数・得点を並べる
+fn draw_hud(screen: &mut Screen, game: &Game, layout: &Layout) {
+    let config = &game.config;
+    let width = layout.board_size(config).0;
+    let y = layout.top - HUD_ROWS;
+    for x in layout.left..layout.left + width {
+        screen.set(x, y, Cell::blank(HUD_BACKGROUND));
+    }
+
+    if config.is_versus() {
+        // 左右に分けて1人ずつ表示する
+        let half = width / config.players as i32;
+        for (i, player) in game.players.iter().enumerate().take(config.players) {
+            let left = layout.left + half * i as i32;
+            screen.set(left, y, Cell::blank(ENERGY_COLORS[i]));
+            let x = draw_energy(
+                screen,
+                left + 1,
+                y,
+                half / 3,
+                player.energy,
+                ENERGY_COLORS[i],
+            );
+            let text = format!(" {}", player.speed_level(config));
+            screen.put_text(x, y, &text, WHITE, HUD_BACKGROUND);
+            let score = format!("{} ", player.score);
+            let x = left + half - score.chars().count() as i32;
+            screen.put_text(x, y, &score, WHITE, HUD_BACKGROUND);
+        }
+        return;
+    }
+
+    let player = &game.players[0];
+    let mut x = draw_energy(
+        screen,
+        layout.left,
+        y,
+        ENERGY_BAR_WIDTH,
+        player.energy,
+        ENERGY_COLORS[0],
+    );
+    let text = format!(" {:>2}", player.speed_level(config));
+    x = screen.put_text(x, y, &text, WHITE, HUD_BACKGROUND);
+    for color in FoodColor::all() {
+        // 端末によって幅が変わる記号は使わず、背景色で色を見せる
+        x = screen.put_text(x, y, " ", WHITE, HUD_BACKGROUND);
+        x = screen.put_text(x, y, " ", WHITE, get_draw_color(&color));
+        let text = format!("{:>3}", player.ate_counts[&color]);
+        x = screen.put_text(x, y, &text, WHITE, HUD_BACKGROUND);
+    }
+    let score = player.score.to_string();
+    let x = (layout.left + width - score.chars().count() as i32).max(x + 1);
+    screen.put_text(x, y, &score, WHITE, HUD_BACKGROUND);
+}
+
+// 満腹度の棒。描き終わった次のxを返す
+fn draw_energy(screen: &mut Screen, x: i32, y: i32, width: i32, energy: i32, color: Color) -> i32 {
+    let filled = width * energy.max(0) / ENERGY_MAX;
+    for i in 0..width {
+        let bg = if i < filled { color } else { EMPTY_ENERGY };
+        screen.set(x + i, y, Cell::blank(bg));
+    }
+    x + width
+}
+
+// 24ビットカラーが使えない端末のための256色の番号。6x6x6の色とグレーの近い方
+pub fn ansi256(color: Color) -> u8 {
+    let level = |v: u8| match v {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (v as i32 - 35) / 40,
+    };
+    let value = |level: i32| if level == 0 { 0 } else { 55 + level * 40 };
+    let (r, g, b) = (level(color.r), level(color.g), level(color.b));
+    let cube = (value(r), value(g), value(b));
+
+    let average = (color.r as i32 + color.g as i32 + color.b as i32) / 3;
+    let gray_level = ((average - 8) / 10).clamp(0, 23);
+    let gray = 8 + gray_level * 10;
+
+    let distance = |(r2, g2, b2): (i32, i32, i32)| {
+        (color.r as i32 - r2).pow(2) + (color.g as i32 - g2).pow(2) + (color.b as i32 - b2).pow(2)
+    };
+    if distance((gray, gray, gray)) < distance(cube) {
+        (232 + gray_level) as u8
+    } else {
+        (16 + r * 36 + g * 6 + b) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Food, Point};
+
+    fn view(game: &Game, columns: i32, rows: i32) -> TerminalView {
+        TerminalView::new(&game.config, columns, rows).unwrap()
+    }
+
+    #[test]
+    fn layout_scales_to_the_terminal() {
+        let config = GameConfig::default();
+        // 21x20の盤面は1倍で枠を含めて23x12文字、上下の行を足して23x14文字
+        let layout = Layout::fit(&config, 80, 24).unwrap();
+        assert_eq!(layout.scale, 2);
+        assert_eq!(layout.board_size(&config), (44, 22));
+        assert_eq!(Layout::fit(&config, 23, 14).unwrap().scale, 1);
+        assert_eq!(Layout::fit(&config, 200, 60).unwrap().scale, 5);
+        assert!(Layout::fit(&config, 22, 40).is_err());
+        assert!(Layout::fit(&config, 80, 13).is_err());
+    }
+
+    #[test]
+    fn layout_centers_the_board() {
+        let config = GameConfig::default();
+        let layout = Layout::fit(&config, 30, 14).unwrap();
+        assert_eq!((layout.left, layout.top), (3, HUD_ROWS));
+    }
+
+    #[test]
+    fn cells_use_the_same_colors_as_sdl() {
+        let mut game = Game::with_seed(0);
+        game.foods = vec![Food {
+            color: FoodColor::Blue,
+            p: Point::new(0, 0),
+            is_exist: true,
+        }];
+        let mut view = view(&game, 23, 14);
+        let screen = view.draw(&game, "").unwrap();
+        let (left, top) = (view.layout.left + FRAME, view.layout.top + FRAME);
+        // 1倍では1文字の上半分が1マス
+        let cell = screen.cell(left, top);
+        assert_eq!(cell.ch, UPPER_HALF);
+        assert_eq!(cell.fg, get_draw_color(&FoodColor::Blue));
+        assert_eq!(cell.bg, BACKGROUND);
+        assert_eq!(screen.cell(left + 1, top), Cell::blank(BACKGROUND));
+        let head = &game.players[0].p;
+        let cell = screen.cell(left + head.x, top + head.y / 2);
+        let color = if head.y % 2 == 0 { cell.fg } else { cell.bg };
+        assert_eq!(color, render::PLAYER_COLORS[0]);
+    }
+
+    #[test]
+    fn frame_shows_the_boundary() {
+        let game = Game::with_seed(0);
+        let screen = view(&game, 23, 14).draw(&game, "").unwrap();
+        assert_eq!(screen.cell(0, HUD_ROWS).ch, '┌');
+        assert_eq!(screen.cell(22, 12).ch, '┘');
+
+        let config = GameConfig {
+            boundary: Boundary::Walls,
+            ..GameConfig::default()
+        };
+        let game = Game::with_config(config, 0);
+        let screen = view(&game, 23, 14).draw(&game, "").unwrap();
+        assert_eq!(screen.cell(0, HUD_ROWS), Cell::blank(HUD_BACKGROUND));
+    }
+
+    #[test]
+    fn hud_and_status_are_drawn_around_the_board() {
+        let mut game = Game::with_seed(0);
+        game.players[0].energy = ENERGY_MAX / 2;
+        game.players[0].score = 1234;
+        game.players[0].ate_counts.insert(FoodColor::Red, 7);
+        let screen = view(&game, 80, 24).draw(&game, "PAUSED").unwrap();
+        let left = (80 - 44) / 2;
+        assert_eq!(screen.cell(left, 0).bg, ENERGY_COLORS[0]);
+        assert_eq!(screen.cell(left + 4, 0).bg, ENERGY_COLORS[0]);
+        assert_eq!(screen.cell(left + 5, 0).bg, EMPTY_ENERGY);
+        let hud = screen.line(0);
+        assert!(hud.contains("   0    7    0    0"), "{:?}", hud);
+        let red = screen.cell(left + 13 + 6, 0);
+        assert_eq!(red.bg, get_draw_color(&FoodColor::Red));
+        assert!(hud.trim_end().ends_with("1234"), "{:?}", hud);
+        assert_eq!(hud.trim_end().chars().count() as i32, left + 44);
+        assert_eq!(screen.line(1 + 22).trim(), "PAUSED");
+    }
+
+    #[test]
+    fn versus_hud_is_split() {
+        let config = GameConfig {
+            players: 2,
+            ..GameConfig::default()
+        };
+        let game = Game::with_config(config, 0);
+        let screen = view(&game, 23, 14).draw(&game, "").unwrap();
+        assert_eq!(screen.cell(0, 0).bg, ENERGY_COLORS[0]);
+        assert_eq!(screen.cell(11, 0).bg, ENERGY_COLORS[1]);
+    }
+
+    #[test]
+    fn changes_are_grouped_by_row() {
+        let mut prev = Screen::new(5, 2);
+        prev.put_text(0, 0, "ab", WHITE, BLACK);
+        let mut screen = prev.clone();
+        screen.put_text(1, 0, "XY", WHITE, BLACK);
+        screen.put_text(4, 0, "Z", WHITE, BLACK);
+        screen.put_text(0, 1, "W", WHITE, BLACK);
+        let spans = screen.changes(Some(&prev));
+        let summary: Vec<(i32, i32, String)> = spans
+            .iter()
+            .map(|x| (x.x, x.y, x.cells.iter().map(|c| c.ch).collect()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, 0, "XY".to_string()),
+                (4, 0, "Z".to_string()),
+                (0, 1, "W".to_string())
+            ]
+        );
+        assert!(screen.changes(Some(&screen)).is_empty());
+        assert_eq!(screen.changes(None).len(), 2);
+        assert_eq!(screen.changes(Some(&Screen::new(4, 2))).len(), 2);
+    }
+
+    #[test]
+    fn ansi256_picks_the_nearest_color() {
+        assert_eq!(ansi256(BLACK), 16);
+        assert_eq!(ansi256(WHITE), 231);
+        assert_eq!(ansi256(Color::rgb(255, 0, 0)), 196);
+        assert_eq!(ansi256(Color::rgb(128, 128, 128)), 244);
+        assert_eq!(ansi256(get_draw_color(&FoodColor::Blue)), 105);
+    }
+}
@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+// 描く速さとは関係なく、決まった間隔 (tick) ごとにゲームを1フレーム進めるための時計
+// 経過時間を溜めておき、tickの分だけ取り出す。遅れても最大max_ticksまでしか取り戻さない
+#[derive(Clone, Debug)]
+pub struct FixedTimestep {
+    pub tick: Duration,
+    pub max_ticks: u32,
+    accumulator: Duration,
+}
+
+impl FixedTimestep {
+    pub fn new(tick: Duration, max_ticks: u32) -> Self {
+        FixedTimestep {
+            tick,
+            max_ticks,
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    // 1秒にrate回
+    pub fn per_second(rate: u32, max_ticks: u32) -> Self {
+        Self::new(Duration::from_secs(1) / rate, max_ticks)
+    }
+
+    // elapsedだけ時間が進んだときに進めるフレーム数
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.accumulator += elapsed;
+        let mut ticks = 0;
+        while self.accumulator >= self.tick {
+            self.accumulator -= self.tick;
+            ticks += 1;
+            if ticks == self.max_ticks {
+                // 取り戻せない分は捨てる。止まっていた後に早送りにならないように
+                self.accumulator = Duration::min(self.accumulator, self.tick / 2);
+                break;
+            }
+        }
+        ticks
+    }
+
+    // 前のフレームから次のフレームまでの進み具合 (0以上1未満)
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.tick.as_secs_f32()
+    }
+
+    // 次のフレームまでの時間
+    pub fn remaining(&self) -> Duration {
+        self.tick.saturating_sub(self.accumulator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn ticks_do_not_depend_on_render_rate() {
+        // 10msごとのtickを、4msごとと25msごとに描いても同じ回数だけ進む
+        let mut fast = FixedTimestep::new(ms(10), 5);
+        let fast_ticks: u32 = (0..250).map(|_| fast.advance(ms(4))).sum();
+        let mut slow = FixedTimestep::new(ms(10), 5);
+        let slow_ticks: u32 = (0..40).map(|_| slow.advance(ms(25))).sum();
+        assert_eq!(fast_ticks, 100);
+        assert_eq!(slow_ticks, 100);
+    }
+
+    #[test]
+    fn alpha_and_remaining_follow_the_accumulator() {
+        let mut timestep = FixedTimestep::new(ms(10), 5);
+        assert_eq!(timestep.advance(ms(14)), 1);
+        assert!((timestep.alpha() - 0.4).abs() < 1e-6);
+        assert_eq!(timestep.remaining(), ms(6));
+        assert_eq!(timestep.advance(ms(6)), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn catch_up_is_limited() {
+        let mut timestep = FixedTimestep::new(ms(10), 5);
+        assert_eq!(timestep.advance(ms(1000)), 5);
+        assert!(timestep.alpha() <= 0.5);
+        assert_eq!(timestep.advance(ms(10)), 1);
+    }
+}